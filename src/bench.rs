@@ -0,0 +1,64 @@
+use std::{
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+const TARGET: Duration = Duration::from_millis(500);
+/// Slow cases still run this often, so one unlucky run can't decide them.
+const MIN_RUNS: u32 = 20;
+
+/// Times `f` by running it repeatedly for about half a second, and at least
+/// [`MIN_RUNS`] times, after a warm-up call, then prints the mean time per
+/// call. Meant to be run from a release build via the `bench` command.
+pub fn bench<O>(name: &str, mut f: impl FnMut() -> O) {
+    black_box(f());
+    let start = Instant::now();
+    let mut runs = 0u32;
+    while runs < MIN_RUNS || start.elapsed() < TARGET {
+        black_box(f());
+        runs += 1;
+    }
+    let mean = start.elapsed() / runs;
+    println!("{name:<48} {mean:>12.2?} ({runs} runs)");
+}
+
+pub fn run() {
+    crate::day3::bench_parsers();
+}
+
+/// A fixed xorshift sequence, so benchmarks and tests that want arbitrary
+/// values see the same ones on every run.
+pub fn pseudo_random() -> impl Iterator<Item = u64> {
+    let mut state = 0x2545_f491_4f6c_dd1d_u64;
+    std::iter::repeat_with(move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    })
+}
+
+/// A pseudo-random schematic with roughly the density of the puzzle input.
+pub fn generate_schematic(lines: usize, cols: usize) -> String {
+    let mut random = pseudo_random();
+    let mut next = move || random.next().unwrap();
+    let mut schematic = String::with_capacity(lines * (cols + 1));
+    for _ in 0..lines {
+        let mut col = 0;
+        while col < cols {
+            match next() % 16 {
+                0..=1 => {
+                    let digits = (1 + next() % 3).min((cols - col) as u64);
+                    (0..digits).for_each(|_| schematic.push((b'0' + (next() % 10) as u8) as char));
+                    col += digits as usize;
+                    continue;
+                }
+                2 => schematic.push(['*', '#', '+', '$', '/', '='][(next() % 6) as usize]),
+                _ => schematic.push('.'),
+            }
+            col += 1;
+        }
+        schematic.push('\n');
+    }
+    schematic
+}
@@ -0,0 +1,140 @@
+// Day8 uses find_cycle; Brent's variant and extrapolation wait for a puzzle.
+#![allow(dead_code)]
+
+use std::{collections::HashMap, hash::Hash};
+
+/// The shape of an iterated sequence `x0, f(x0), f(f(x0)), ...` that
+/// eventually repeats: `tail` steps lead into a loop of `length` states.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cycle {
+    pub tail: usize,
+    pub length: usize,
+    /// Steps in `tail..tail + length` whose state matched the predicate.
+    pub hits: Vec<usize>,
+}
+impl Cycle {
+    /// The earliest step whose state is the same as at step `n`.
+    pub fn equivalent_step(&self, n: usize) -> usize {
+        if n < self.tail {
+            n
+        } else {
+            self.tail + (n - self.tail) % self.length
+        }
+    }
+}
+
+/// Brent's algorithm: finds the cycle holding only two states at a time, at
+/// the cost of stepping through the sequence a few times over.
+pub fn brent<S: Clone + PartialEq>(
+    start: S,
+    mut step: impl FnMut(&S) -> S,
+    mut pred: impl FnMut(&S) -> bool,
+) -> Cycle {
+    let mut power = 1;
+    let mut length = 1;
+    let mut tortoise = start.clone();
+    let mut hare = step(&start);
+    while tortoise != hare {
+        if power == length {
+            tortoise = hare.clone();
+            power *= 2;
+            length = 0;
+        }
+        hare = step(&hare);
+        length += 1;
+    }
+
+    let mut tortoise = start.clone();
+    let mut hare = start;
+    for _ in 0..length {
+        hare = step(&hare);
+    }
+    let mut tail = 0;
+    while tortoise != hare {
+        tortoise = step(&tortoise);
+        hare = step(&hare);
+        tail += 1;
+    }
+
+    let mut hits = Vec::new();
+    for i in tail..tail + length {
+        if pred(&tortoise) {
+            hits.push(i);
+        }
+        tortoise = step(&tortoise);
+    }
+    Cycle { tail, length, hits }
+}
+
+/// Finds the cycle by remembering every state, stepping through the sequence
+/// only once. Also returns the states from step 0 up to the end of the first
+/// pass around the loop.
+pub fn find_cycle<S: Clone + Eq + Hash>(
+    start: S,
+    mut step: impl FnMut(&S) -> S,
+    mut pred: impl FnMut(&S) -> bool,
+) -> (Cycle, Vec<S>) {
+    let mut seen = HashMap::new();
+    let mut states = Vec::new();
+    let mut state = start;
+    let tail = loop {
+        if let Some(&i) = seen.get(&state) {
+            break i;
+        }
+        seen.insert(state.clone(), states.len());
+        let next = step(&state);
+        states.push(state);
+        state = next;
+    };
+    let hits = (tail..states.len()).filter(|&i| pred(&states[i])).collect();
+    let cycle = Cycle {
+        tail,
+        length: states.len() - tail,
+        hits,
+    };
+    (cycle, states)
+}
+
+/// The state after `n` steps, skipping whole trips around the loop.
+pub fn nth_state<S: Clone + PartialEq>(start: S, mut step: impl FnMut(&S) -> S, n: usize) -> S {
+    let cycle = brent(start.clone(), &mut step, |_| false);
+    let mut state = start;
+    for _ in 0..cycle.equivalent_step(n) {
+        state = step(&state);
+    }
+    state
+}
+
+#[test]
+fn cycles() {
+    let step = |&x: &u32| (x * x + 1) % 255;
+    for start in 0..255 {
+        let naive = (0..600).scan(start, |x, _| Some(std::mem::replace(x, step(x))));
+        let naive = naive.collect::<Vec<_>>();
+        let tail = (0..).find(|&i| naive[i + 1..].contains(&naive[i])).unwrap();
+        let length = naive[tail + 1..]
+            .iter()
+            .position(|&x| x == naive[tail])
+            .unwrap()
+            + 1;
+
+        let even = |x: &u32| x % 2 == 0;
+        let cycle = brent(start, step, even);
+        assert_eq!((cycle.tail, cycle.length), (tail, length));
+        let (hashed, states) = find_cycle(start, step, even);
+        assert_eq!(hashed, cycle);
+        assert_eq!(states, naive[..tail + length]);
+        for &i in &cycle.hits {
+            assert!(even(&naive[i]));
+        }
+        for n in [0, tail, tail + length, 599] {
+            assert_eq!(nth_state(start, step, n), naive[n]);
+            assert_eq!(states[cycle.equivalent_step(n)], naive[n]);
+        }
+    }
+    let (cycle, states) = find_cycle(3, step, |_| false);
+    assert_eq!(
+        nth_state(3, step, 1 << 40),
+        states[cycle.equivalent_step(1 << 40)]
+    );
+}
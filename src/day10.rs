@@ -1,12 +1,15 @@
-use crate::AocDay;
+use crate::{
+    grid::{Dir, Grid},
+    linespan::parse_or_report,
+    parse::grid_p,
+    AocDay,
+};
 
-use itertools::Itertools;
 use num_enum::TryFromPrimitive;
+use tracing::{debug, instrument};
 use winnow::{
-    ascii::{multispace1, newline},
-    combinator::{repeat, separated},
+    error::{StrContext, StrContextValue},
     prelude::*,
-    token::any,
 };
 
 pub struct Day10;
@@ -15,36 +18,33 @@ impl AocDay for Day10 {
         include_str!("data/day10.txt")
     }
 
-    fn part1(&self, mut input: &str) -> usize {
-        let grid = grid_p(&mut input).unwrap();
-        let mut coord = grid
-            .iter_with_coords()
-            .find_map(|(coord, tile)| (tile == Tile::Start).then_some(coord))
-            .unwrap();
-        let mut dir = [Dir::N, Dir::S, Dir::E, Dir::W]
+    fn part1(&self, input: &str) -> usize {
+        let grid = parse_or_report(input, tiles_p);
+        let mut coord = grid.position(|&tile| tile == Tile::Start).unwrap();
+        let mut dir = Dir::ALL
             .into_iter()
             .find_map(|start_dir| {
-                let mut coord = coord;
-                let mut dir = start_dir;
-                coord.walk(dir).ok()?;
-                dir.rotate(grid.get(coord)?).ok()?;
+                let next = grid.step(coord, start_dir)?;
+                turn(start_dir, grid[next])?;
                 Some(start_dir)
             })
             .unwrap();
+        let start_dir = dir;
         let mut num = 0;
         loop {
-            coord.walk(dir).unwrap();
+            coord = grid.step(coord, dir).unwrap();
             num += 1;
-            let tile = grid.get(coord).unwrap();
+            let tile = grid[coord];
             if tile == Tile::Start {
                 break;
             }
-            dir.rotate(tile).unwrap();
+            dir = turn(dir, tile).unwrap();
         }
+        debug!(length = num, ?start_dir, "walked loop");
         num / 2
     }
 
-    fn part2(&self, mut input: &str) -> usize {
+    fn part2(&self, _input: &str) -> usize {
         0
     }
 }
@@ -55,48 +55,27 @@ fn part1() {
 }
 
 #[test]
-fn part2() {
-    assert_eq!(Day10.part2(TEST_INPUT), 0)
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-struct Coord(usize, usize);
-impl Coord {
-    fn walk(&mut self, dir: Dir) -> Result<(), ()> {
-        *self = match dir {
-            Dir::N => Coord(self.0, self.1.checked_sub(1).ok_or(())?),
-            Dir::S => Coord(self.0, self.1 + 1),
-            Dir::E => Coord(self.0 + 1, self.1),
-            Dir::W => Coord(self.0.checked_sub(1).ok_or(())?, self.1),
-        };
-        Ok(())
-    }
+fn part1_search() {
+    let grid = parse_or_report(TEST_INPUT, tiles_p);
+    let opens = |tile: Tile, dir: Dir| tile as u8 & dir as u8 != 0;
+    let pipes = |&coord: &crate::grid::Coord| {
+        Dir::ALL
+            .into_iter()
+            .filter(|&dir| opens(grid[coord], dir))
+            .filter_map(|dir| {
+                let next = grid.step(coord, dir)?;
+                opens(grid[next], dir.reverse()).then_some(next)
+            })
+            .collect::<Vec<_>>()
+    };
+    let start = grid.position(|&tile| tile == Tile::Start).unwrap();
+    let reached = crate::search::bfs(start, pipes, |_| false);
+    assert_eq!(reached.costs().values().max(), Some(&8));
 }
 
-struct Grid {
-    grid: Vec<Vec<Tile>>,
-}
-impl Grid {
-    fn get(&self, coord: Coord) -> Option<Tile> {
-        self.grid
-            .get(coord.1)
-            .and_then(|line| line.get(coord.0).copied())
-    }
-    fn get_mut(&mut self, coord: Coord) -> Option<&mut Tile> {
-        self.grid
-            .get_mut(coord.1)
-            .and_then(|line| line.get_mut(coord.0))
-    }
-    fn iter_with_coords<'g>(&'g self) -> impl Iterator<Item = (Coord, Tile)> + 'g {
-        self.grid.iter().enumerate().flat_map(|(y, line)| {
-            line.iter()
-                .enumerate()
-                .map(move |(x, tile)| (Coord(x, y), *tile))
-        })
-    }
-    fn iter_mut(&mut self) -> impl Iterator<Item = &mut Tile> {
-        self.grid.iter_mut().flat_map(|line| line.iter_mut())
-    }
+#[test]
+fn part2() {
+    assert_eq!(Day10.part2(TEST_INPUT), 0)
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
@@ -115,35 +94,24 @@ enum Tile {
     Countable,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
-#[repr(u8)]
-enum Dir {
-    N = 0b1000,
-    S = 0b0100,
-    E = 0b0010,
-    W = 0b0001,
-}
-impl Dir {
-    fn reverse(&mut self) {
-        let odds = (*self as u8) & 0b1010;
-        let evens = (*self as u8) & 0b0101;
-        *self = (odds >> 1 | evens << 1).try_into().unwrap()
-    }
-    fn rotate(&mut self, tile: Tile) -> Result<(), ()> {
-        self.reverse();
-        *self = ((tile as u8) & !(*self as u8)).try_into().map_err(|_| ())?;
-        Ok(())
-    }
+/// Direction of travel after following `tile` when entering it heading
+/// `dir`, or `None` if the pipe has no opening facing back the way we came.
+fn turn(dir: Dir, tile: Tile) -> Option<Dir> {
+    ((tile as u8) & !(dir.reverse() as u8)).try_into().ok()
 }
 
-fn grid_p(input: &mut &str) -> PResult<Grid> {
-    separated(1.., repeat::<_, _, Vec<_>, _, _>(1.., tile_p), multispace1)
-        .map(|grid| Grid { grid })
+#[instrument(level = "debug", skip_all)]
+fn tiles_p(input: &mut &str) -> PResult<Grid<Tile>> {
+    grid_p(tile)
+        .context(StrContext::Label("grid"))
+        .context(StrContext::Expected(StrContextValue::Description(
+            "pipe tile (|, -, L, J, 7, F, . or S)",
+        )))
         .parse_next(input)
 }
 
-fn tile_p(input: &mut &str) -> PResult<Tile> {
-    any.verify_map(|c: char| match c {
+fn tile(c: char) -> Option<Tile> {
+    match c {
         '|' => Some(Tile::NS),
         '-' => Some(Tile::EW),
         'L' => Some(Tile::NE),
@@ -153,8 +121,7 @@ fn tile_p(input: &mut &str) -> PResult<Tile> {
         '.' => Some(Tile::Ground),
         'S' => Some(Tile::Start),
         _ => None,
-    })
-    .parse_next(input)
+    }
 }
 
 #[cfg(test)]
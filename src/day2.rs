@@ -1,14 +1,18 @@
-use std::str::FromStr;
+use std::io::BufRead;
 
+use tracing::instrument;
 use winnow::{
-    ascii::{alpha1, digit1, space0, space1},
-    combinator::{delimited, separated, separated_pair},
-    error::ContextError,
+    ascii::{digit1, space0, space1},
+    combinator::{alt, delimited, separated, separated_pair},
+    error::{StrContext, StrContextValue},
     prelude::*,
     stream::Accumulate,
 };
 
-use crate::AocDay;
+use crate::{
+    linespan::{unlocated, LineReader, ParseReport},
+    AocDay, StreamingDay,
+};
 
 pub struct Day2;
 
@@ -18,9 +22,22 @@ impl AocDay for Day2 {
     }
 
     fn part1(&self, input: &str) -> usize {
+        self.part1_stream(&mut input.as_bytes())
+    }
+
+    fn part2(&self, input: &str) -> usize {
+        self.part2_stream(&mut input.as_bytes())
+    }
+
+    fn streaming(&self) -> Option<&dyn StreamingDay> {
+        Some(self)
+    }
+}
+impl StreamingDay for Day2 {
+    fn part1_stream(&self, input: &mut dyn BufRead) -> usize {
+        let mut failures = ParseReport::default();
         let mut sum = 0;
-        for line in input.lines() {
-            let game = line.parse::<Game>().unwrap();
+        for game in LineReader::new(input).parse_each(unlocated(game_p), &mut failures) {
             if game.pulls.iter().all(|pull| {
                 pull.red <= MAX_CUBES.red
                     && pull.green <= MAX_CUBES.green
@@ -29,13 +46,14 @@ impl AocDay for Day2 {
                 sum += game.id;
             }
         }
+        failures.finish();
         sum
     }
 
-    fn part2(&self, input: &str) -> usize {
+    fn part2_stream(&self, input: &mut dyn BufRead) -> usize {
+        let mut failures = ParseReport::default();
         let mut sum = 0;
-        for line in input.lines() {
-            let game = line.parse::<Game>().unwrap();
+        for game in LineReader::new(input).parse_each(unlocated(game_p), &mut failures) {
             let mut min_set = CubeSet {
                 red: 0,
                 green: 0,
@@ -48,6 +66,7 @@ impl AocDay for Day2 {
             }
             sum += min_set.red * min_set.green * min_set.blue;
         }
+        failures.finish();
         sum
     }
 }
@@ -86,24 +105,25 @@ struct CubeSet {
     blue: usize,
 }
 
-impl FromStr for Game {
-    type Err = winnow::error::ErrMode<ContextError>;
-
-    fn from_str(mut s: &str) -> Result<Self, Self::Err> {
-        game_p(&mut s)
-    }
-}
-
+#[instrument(level = "trace", skip_all)]
 fn game_p(input: &mut &str) -> PResult<Game> {
     let pulls_p = separated(1.., delimited(space0, cube_set_p, space0), ';');
-    let game_id_p = delimited("Game ", digit1.parse_to(), (':', space0));
-    let (id, pulls) = (game_id_p, pulls_p).parse_next(input)?;
+    let game_id_p =
+        delimited("Game ", digit1.parse_to(), (':', space0)).context(StrContext::Label("game id"));
+    let (id, pulls) = (game_id_p, pulls_p)
+        .context(StrContext::Label("game"))
+        .parse_next(input)?;
     Ok(Game { id, pulls })
 }
 
 fn cube_set_p(input: &mut &str) -> PResult<CubeSet> {
-    let num_and_color_p = separated_pair(digit1.parse_to(), space1, alpha1);
-    separated(1..=3, delimited(space0, num_and_color_p, space0), ',').parse_next(input)
+    let color_p = alt(("red", "green", "blue")).context(StrContext::Expected(
+        StrContextValue::Description("red, green or blue"),
+    ));
+    let num_and_color_p = separated_pair(digit1.parse_to(), space1, color_p);
+    separated(1..=3, delimited(space0, num_and_color_p, space0), ',')
+        .context(StrContext::Label("cube set"))
+        .parse_next(input)
 }
 
 impl Accumulate<(usize, &str)> for CubeSet {
@@ -1,18 +1,23 @@
-use std::{
-    collections::{HashMap, HashSet},
-    fmt::Debug,
-};
+use std::collections::HashMap;
 
-use itertools::iproduct;
+use itertools::Itertools;
+use tracing::instrument;
 use winnow::{
     ascii::digit1,
-    combinator::{repeat, repeat_till0},
+    combinator::{repeat, repeat_till0, rest, terminated},
     prelude::*,
+    stream::{AsChar, ParseSlice, Stream, StreamIsPartial},
     token::{any, none_of},
 };
 
 use crate::{
-    linespan::{linespan, with_linespan, LineLocated, LineSpan},
+    bench::{bench, generate_schematic},
+    grid::{Coord, Grid},
+    linespan::{
+        parse_located_or_report, parse_or_report, spanned, LineIndex, LineLocated, LineStream,
+        Spanned,
+    },
+    parse::grid_p,
     AocDay,
 };
 
@@ -23,34 +28,26 @@ impl AocDay for Day3 {
     }
 
     fn part1(&self, input: &str) -> usize {
-        let (lines, cols) = input.lines().map(str::len).enumerate().last().unwrap();
-        let symbols = symbols_p
-            .parse_next(&mut LineLocated::new(input))
-            .unwrap()
-            .into_iter()
-            .map(|sym| (sym.span.start, sym.line))
-            .collect::<HashSet<_>>();
-        numlocs_p(&mut LineLocated::new(input))
-            .unwrap()
+        let grid = parse_or_report(input, grid_p(Some));
+        parse_located_or_report(LineLocated::new(input), numlocs_p)
             .into_iter()
-            .filter(|nl| bounding_box(&nl.linespan, lines, cols).any(|xy| symbols.contains(&xy)))
-            .map(|nl| nl.number)
+            .filter(|num| adjacent(&grid, num).any(|xy| is_symbol(grid[xy])))
+            .map(Spanned::into_inner)
             .sum()
     }
 
     fn part2(&self, input: &str) -> usize {
-        let (lines, cols) = input.lines().map(str::len).enumerate().last().unwrap();
-        let mut inters = HashMap::<(usize, usize), Vec<usize>>::new();
-        numlocs_p(&mut LineLocated::new(input))
-            .unwrap()
-            .into_iter()
-            .flat_map(|nl| bounding_box(&nl.linespan, lines, cols).map(move |xy| (xy, nl.number)))
-            .for_each(|(xy, num)| inters.entry(xy).or_default().push(num));
-        gears_p(&mut LineLocated::new(input))
-            .unwrap()
+        let grid = parse_or_report(input, grid_p(Some));
+        let mut gears = HashMap::<Coord, Vec<usize>>::new();
+        parse_located_or_report(LineLocated::new(input), numlocs_p)
             .into_iter()
-            .map(|gear| (gear.span.start, gear.line))
-            .filter_map(|xy| inters.get(&xy))
+            .flat_map(|num| {
+                let touching = adjacent(&grid, &num).filter(|&xy| grid[xy] == '*');
+                touching.map(move |xy| (xy, *num))
+            })
+            .for_each(|(xy, num)| gears.entry(xy).or_default().push(num));
+        gears
+            .values()
             .filter(|ns| ns.len() == 2)
             .map(|ns| ns[0] * ns[1])
             .sum()
@@ -79,54 +76,105 @@ const TEST_INPUT: &str = r#"467..114..
 ...$.*....
 .664.598.."#;
 
-fn bounding_box(
-    LineSpan { line, span }: &LineSpan,
-    num_lines: usize,
-    num_cols: usize,
-) -> impl Iterator<Item = (usize, usize)> {
-    let first_line = line.saturating_sub(1);
-    let last_line = (line + 1).min(num_lines - 1);
-    let first_col = span.start.saturating_sub(1);
-    let last_col = span.end.min(num_cols - 1);
-    iproduct!(first_col..=last_col, first_line..=last_line)
+/// Every cell touching `num`, including diagonally, each listed once.
+fn adjacent<'g, T>(grid: &'g Grid<char>, num: &Spanned<T>) -> impl Iterator<Item = Coord> + 'g {
+    let line = num.line;
+    num.span
+        .clone()
+        .flat_map(move |x| grid.neighbours8(Coord(x, line)))
+        .unique()
 }
 
-#[derive(Debug)]
-struct NumLoc {
-    number: usize,
-    linespan: LineSpan,
+/// Anything but a digit or an empty `.` cell.
+fn is_symbol(c: char) -> bool {
+    !c.is_ascii_digit() && c != '.'
 }
 
-fn numlocs_p(input: &mut LineLocated) -> PResult<Vec<NumLoc>> {
-    repeat(
-        0..,
-        repeat_till0(any, numloc_p).map(|(_, b): (String, _)| b),
-    )
-    .parse_next(input)
+/// Inputs the schematic parsers run over: `&str` or `&[u8]`.
+trait Schematic:
+    LineStream + StreamIsPartial + Stream<Token: AsChar + Clone, Slice: ParseSlice<usize>>
+{
 }
+impl<I> Schematic for I where
+    I: LineStream + StreamIsPartial + Stream<Token: AsChar + Clone, Slice: ParseSlice<usize>>
+{
+}
+
+pub fn bench_parsers() {
+    let input = Day3.input();
+    bench("day3 numlocs_p &str", || {
+        numlocs_p.parse_next(&mut LineLocated::new(input))
+    });
+    bench("day3 numlocs_p &[u8]", || {
+        numlocs_p.parse_next(&mut LineLocated::new(input.as_bytes()))
+    });
+    bench("day3 symbols_p &str", || {
+        symbols_p.parse_next(&mut LineLocated::new(input))
+    });
+    bench("day3 symbols_p &[u8]", || {
+        symbols_p.parse_next(&mut LineLocated::new(input.as_bytes()))
+    });
 
-fn numloc_p(input: &mut LineLocated) -> PResult<NumLoc> {
-    with_linespan(digit1.parse_to::<usize>())
-        .map(|(number, linespan)| NumLoc { number, linespan })
-        .parse_next(input)
+    let large = generate_schematic(2000, 2000);
+    // Line tracking for three span parsers over one document, which is all
+    // a shared index changes: an index and a lookup per non-empty cell.
+    // Parsing itself costs the same either way.
+    let offsets = large
+        .bytes()
+        .enumerate()
+        .filter(|&(_, b)| b != b'.' && b != b'\n')
+        .map(|(offset, _)| offset)
+        .collect_vec();
+    bench("day3 large locating, index per parser + bsearch", || {
+        (0..3)
+            .map(|_| {
+                let index = LineIndex::new(large.as_bytes());
+                offsets
+                    .iter()
+                    .filter_map(|&o| index.line_of(o))
+                    .sum::<usize>()
+            })
+            .sum::<usize>()
+    });
+    bench("day3 large locating, shared index + cursor", || {
+        let index = LineIndex::new(large.as_bytes());
+        (0..3)
+            .map(|_| {
+                let mut line = 0;
+                offsets
+                    .iter()
+                    .filter_map(|&o| {
+                        line = index.line_of_near(o, line)?;
+                        Some(line)
+                    })
+                    .sum::<usize>()
+            })
+            .sum::<usize>()
+    });
 }
 
-fn gears_p(input: &mut LineLocated) -> PResult<Vec<LineSpan>> {
-    repeat(
-        0..,
-        repeat_till0(any, linespan('*')).map(|(_, b): (String, _)| b),
+#[instrument(level = "debug", skip_all)]
+fn numlocs_p<I: Schematic>(input: &mut LineLocated<I>) -> PResult<Vec<Spanned<usize>>> {
+    terminated(
+        repeat(0.., repeat_till0(any, numloc_p).map(|((), b)| b)),
+        rest,
     )
     .parse_next(input)
 }
 
-fn symbols_p(input: &mut LineLocated) -> PResult<Vec<LineSpan>> {
-    repeat(
-        0..,
-        repeat_till0(any, symbol_p).map(|(_, b): (String, LineSpan)| b),
+fn numloc_p<I: Schematic>(input: &mut LineLocated<I>) -> PResult<Spanned<usize>> {
+    spanned(digit1.parse_to::<usize>()).parse_next(input)
+}
+
+#[instrument(level = "debug", skip_all)]
+fn symbols_p<I: Schematic>(input: &mut LineLocated<I>) -> PResult<Vec<Spanned<()>>> {
+    terminated(
+        repeat(0.., repeat_till0(any, symbol_p).map(|((), b)| b)),
+        rest,
     )
     .parse_next(input)
 }
 
-fn symbol_p(input: &mut LineLocated) -> PResult<LineSpan> {
-    linespan(none_of(b"0123456789.")).parse_next(input)
+fn symbol_p<I: Schematic>(input: &mut LineLocated<I>) -> PResult<Spanned<()>> {
+    spanned(none_of(b"0123456789.").void()).parse_next(input)
 }
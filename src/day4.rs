@@ -1,10 +1,17 @@
+use std::{collections::VecDeque, io::BufRead};
+
+use tracing::instrument;
 use winnow::{
     ascii::{digit1, space0, space1},
-    combinator::separated,
+    error::{StrContext, StrContextValue},
     PResult, Parser,
 };
 
-use crate::AocDay;
+use crate::{
+    linespan::{unlocated, LineReader, ParseReport},
+    parse::uints_p,
+    AocDay, StreamingDay,
+};
 
 pub struct Day4;
 impl AocDay for Day4 {
@@ -13,19 +20,44 @@ impl AocDay for Day4 {
     }
 
     fn part1(&self, input: &str) -> usize {
-        input
-            .lines()
-            .map(|line| 2usize.pow(card_wins(line) as u32) / 2)
-            .sum()
+        self.part1_stream(&mut input.as_bytes())
     }
 
     fn part2(&self, input: &str) -> usize {
-        let mut ns = input.lines().map(card_wins).rev().collect::<Vec<_>>();
-        for i in 0..ns.len() {
-            let wins = ns[i];
-            ns[i] = 1 + (i - wins..i).map(|j| ns[j]).sum::<usize>();
+        self.part2_stream(&mut input.as_bytes())
+    }
+
+    fn streaming(&self) -> Option<&dyn StreamingDay> {
+        Some(self)
+    }
+}
+impl StreamingDay for Day4 {
+    fn part1_stream(&self, input: &mut dyn BufRead) -> usize {
+        let mut failures = ParseReport::default();
+        let points = LineReader::new(input)
+            .parse_each(unlocated(line_p), &mut failures)
+            .map(|card| 2usize.pow(card_wins(card) as u32) / 2)
+            .sum();
+        failures.finish();
+        points
+    }
+
+    fn part2_stream(&self, input: &mut dyn BufRead) -> usize {
+        // Copies won so far for the cards still to come, nearest first.
+        let mut owed = VecDeque::<usize>::new();
+        let mut failures = ParseReport::default();
+        let mut total = 0;
+        for card in LineReader::new(input).parse_each(unlocated(line_p), &mut failures) {
+            let copies = 1 + owed.pop_front().unwrap_or(0);
+            total += copies;
+            let wins = card_wins(card);
+            if owed.len() < wins {
+                owed.resize(wins, 0);
+            }
+            owed.iter_mut().take(wins).for_each(|n| *n += copies);
         }
-        ns.iter().sum()
+        failures.finish();
+        total
     }
 }
 
@@ -38,26 +70,22 @@ fn part2() {
     assert_eq!(Day4.part2(TEST_INPUT), 30);
 }
 
-fn card_wins(mut line: &str) -> usize {
-    let (c0, c1) = line_p(&mut line).unwrap();
+fn card_wins((c0, c1): (Vec<usize>, Vec<usize>)) -> usize {
     c1.into_iter().filter(|c| c0.contains(c)).count()
 }
 
+#[instrument(level = "trace", skip_all)]
 fn line_p(input: &mut &str) -> PResult<(Vec<usize>, Vec<usize>)> {
     (
-        ("Card", space1, digit1, ':', space0),
-        num_vec_p,
-        (space0, '|', space0),
-        num_vec_p,
+        ("Card", space1, digit1, ':', space0).context(StrContext::Label("card header")),
+        uints_p.context(StrContext::Label("winning numbers")),
+        (space0, '|', space0).context(StrContext::Expected(StrContextValue::CharLiteral('|'))),
+        uints_p.context(StrContext::Label("numbers you have")),
     )
         .map(|(_, c0, _, c1)| (c0, c1))
         .parse_next(input)
 }
 
-fn num_vec_p(input: &mut &str) -> PResult<Vec<usize>> {
-    separated(1.., digit1.parse_to::<usize>(), space1).parse_next(input)
-}
-
 #[cfg(test)]
 const TEST_INPUT: &str = r#"Card 1: 41 48 83 86 17 | 83 86  6 31 17  9 48 53
 Card 2: 13 32 20 16 61 | 61 30 68 82 17 32 24 19
@@ -1,13 +1,21 @@
+use std::{collections::HashMap, ops::RangeInclusive};
+
 use itertools::Itertools;
 use range_set_blaze::RangeSetBlaze;
+use tracing::{debug, instrument, trace, Level};
 use winnow::{
-    ascii::{digit1, multispace0, multispace1, space0, space1},
-    combinator::{preceded, separated},
+    ascii::{alpha1, line_ending, space0, space1},
+    combinator::separated_pair,
+    error::{StrContext, StrContextValue},
     prelude::*,
-    token::take_till,
 };
 
-use crate::AocDay;
+use crate::{
+    linespan::{parse_located_or_report, with_docspan, DocSpan, LineLocated},
+    parse::{blank_lines_p, blocks_p, labelled_p, lines_p, uints_p},
+    search::bfs,
+    AocDay,
+};
 
 pub struct Day5;
 impl AocDay for Day5 {
@@ -15,33 +23,31 @@ impl AocDay for Day5 {
         include_str!("data/day5.txt")
     }
 
-    fn part1(&self, mut input: &str) -> usize {
-        let (seeds, mappers) = input_p(&mut input).unwrap();
-        seeds
-            .into_iter()
-            .map(|seed| {
-                mappers
-                    .iter()
-                    .fold(seed, |seed, mapper| mapper.map_id(seed))
-            })
-            .min()
-            .unwrap()
-    }
-
-    fn part2(&self, mut input: &str) -> usize {
-        let (seeds, mappers) = input_p(&mut input).unwrap();
-        let seed_ranges = seeds
-            .into_iter()
-            .batching(|iter| {
-                let start = iter.next()?;
-                let end = start + iter.next()? - 1;
-                Some(start..=end)
-            })
-            .collect::<RangeSetBlaze<usize>>();
-        let location_ranges = mappers
-            .iter()
-            .fold(seed_ranges, |ranges, mapper| mapper.map_rangeset(ranges));
-        location_ranges.first().unwrap()
+    fn part1(&self, input: &str) -> usize {
+        let almanac = parse_located_or_report(LineLocated::new(input), input_p);
+        almanac.check(false);
+        let seeds = almanac.seeds.iter().copied();
+        let locations = almanac.map("seed", "location", seeds);
+        locations.into_iter().min().unwrap()
+    }
+
+    fn part2(&self, input: &str) -> usize {
+        let almanac = parse_located_or_report(LineLocated::new(input), input_p);
+        almanac.check(true);
+        let chain = almanac.expect_route("seed", "location");
+        let seed_to_location = compose(&chain);
+        let seeds = almanac.seed_ranges().collect::<RangeSetBlaze<usize>>();
+        let location = seeds
+            .ranges()
+            .flat_map(|seeds| seed_to_location.image(seeds))
+            .collect::<RangeSetBlaze<usize>>()
+            .first()
+            .unwrap();
+        if tracing::enabled!(Level::DEBUG) {
+            let closest = almanac.preimage("seed", "location", location..=location);
+            debug!(location, seeds = %(closest & &seeds), "closest seeds");
+        }
+        location
     }
 }
 
@@ -56,25 +62,245 @@ fn part2() {
 
 // ------------- Structs --------------
 
+#[derive(Debug)]
+struct Almanac<'i> {
+    seeds: Vec<usize>,
+    /// In the order the blocks appear, which needn't be the order they apply.
+    mappers: Vec<IdMapper<'i>>,
+    /// For each category, the categories one mapper away and the index of
+    /// that mapper. Where blocks repeat a pair of categories only the first
+    /// is an edge; see [`Problem::DuplicateMap`].
+    edges: HashMap<&'i str, Vec<(&'i str, usize)>>,
+}
+impl<'i> Almanac<'i> {
+    fn new(seeds: Vec<usize>, mappers: Vec<IdMapper<'i>>) -> Self {
+        let mut edges = HashMap::<_, Vec<_>>::new();
+        for (i, mapper) in mappers.iter().enumerate() {
+            let next = edges.entry(mapper.from).or_default();
+            if next.iter().all(|&(to, _)| to != mapper.to) {
+                next.push((mapper.to, i));
+            }
+        }
+        Almanac {
+            seeds,
+            mappers,
+            edges,
+        }
+    }
+    /// Maps `ids` of category `from` to category `to`. Panics if no chain
+    /// of mappers leads there.
+    fn map(&self, from: &str, to: &str, ids: impl IntoIterator<Item = usize>) -> Vec<usize> {
+        let composed = compose(&self.expect_route(from, to));
+        ids.into_iter().map(|id| composed.map_id(id)).collect()
+    }
+    /// Every id of category `from` that lands in `ids` of category `to`,
+    /// e.g. the seeds that end up at a location. Panics if no chain of
+    /// mappers leads there.
+    fn preimage(&self, from: &str, to: &str, ids: RangeInclusive<usize>) -> RangeSetBlaze<usize> {
+        compose(&self.expect_route(from, to)).preimage(ids)
+    }
+    /// The mappers leading from category `from` to `to`, in the order to
+    /// apply them, using as few as possible.
+    fn route(&self, from: &str, to: &str) -> Option<Vec<&IdMapper<'i>>> {
+        let next = |category: &&str| self.next_categories(category);
+        let categories = bfs(from, next, |&category| category == to).path_to(&to)?;
+        let chain = categories.windows(2).map(|pair| {
+            let i = self.edge(pair[0], pair[1]).expect("routes follow edges");
+            &self.mappers[i]
+        });
+        Some(chain.collect())
+    }
+    /// Index of the mapper from category `from` straight to `to`.
+    fn edge(&self, from: &str, to: &str) -> Option<usize> {
+        let next = self.edges.get(from)?;
+        next.iter().find(|&&(next, _)| next == to).map(|&(_, i)| i)
+    }
+    /// Seeds read in pairs of start and length, as part 2 does. Empty ranges
+    /// and ranges running past `usize::MAX` are left out; see
+    /// [`Almanac::problems`].
+    fn seed_ranges(&self) -> impl Iterator<Item = RangeInclusive<usize>> + '_ {
+        self.seeds
+            .chunks_exact(2)
+            .filter_map(|pair| Some(pair[0]..=pair[0].checked_add(pair[1].checked_sub(1)?)?))
+    }
+    /// Everything that would make an answer silently wrong: maps given
+    /// twice, overlapping or empty ranges, ranges that run past `usize::MAX`,
+    /// and with
+    /// `seed_ranges`, a seed list that doesn't pair up into ranges or pairs
+    /// up into empty ones.
+    fn problems(&self, seed_ranges: bool) -> Vec<Problem<'i>> {
+        let mut problems = Vec::new();
+        if seed_ranges {
+            if self.seeds.len() % 2 != 0 {
+                problems.push(Problem::OddSeedList(self.seeds.len()));
+            }
+            for pair in self.seeds.chunks_exact(2) {
+                if pair[1] == 0 {
+                    problems.push(Problem::EmptySeedRange(pair[0]));
+                } else if pair[0].checked_add(pair[1] - 1).is_none() {
+                    problems.push(Problem::SeedOverflow(pair[0], pair[1]));
+                }
+            }
+        }
+        for (n, mapper) in self.mappers.iter().enumerate() {
+            let header = |mapper: &IdMapper| mapper.span.start.line + 1;
+            if let Some(first) = self
+                .edge(mapper.from, mapper.to)
+                .filter(|&first| first != n)
+            {
+                let first = &self.mappers[first];
+                problems.push(Problem::DuplicateMap(
+                    mapper.name,
+                    header(first),
+                    header(mapper),
+                ));
+            }
+            let line = |i: usize| header(mapper) + 1 + i;
+            let mut sources = Vec::new();
+            for (i, range) in mapper.ranges.iter().enumerate() {
+                let problem = if range.len == 0 {
+                    Problem::EmptyRange
+                } else if let (Some(src), Some(_)) = (range.sources(), range.destinations()) {
+                    sources.push((src, line(i)));
+                    continue;
+                } else {
+                    Problem::Overflow
+                };
+                problems.push(problem(mapper.name, line(i)));
+            }
+            sources.sort_by_key(|(src, _)| *src.start());
+            // The range reaching furthest so far, which any overlap is with.
+            let mut furthest: Option<(usize, usize)> = None;
+            for (src, line) in sources {
+                if let Some((end, first)) = furthest {
+                    if *src.start() <= end {
+                        problems.push(Problem::Overlap(
+                            mapper.name,
+                            first.min(line),
+                            first.max(line),
+                        ));
+                    }
+                }
+                if furthest.is_none_or(|(end, _)| *src.end() > end) {
+                    furthest = Some((*src.end(), line));
+                }
+            }
+        }
+        problems
+    }
+    /// Panics listing every one of [`Almanac::problems`], if there are any.
+    fn check(&self, seed_ranges: bool) {
+        let problems = self.problems(seed_ranges);
+        if !problems.is_empty() {
+            let list = problems.iter().map(|p| format!("  {p}")).join("\n");
+            panic!("almanac has {} problem(s):\n{list}", problems.len());
+        }
+    }
+    /// Categories one mapper away from `category`.
+    fn next_categories(&self, category: &str) -> impl Iterator<Item = &'i str> + '_ {
+        let next = self.edges.get(category).map_or(&[][..], Vec::as_slice);
+        next.iter().map(|&(to, _)| to)
+    }
+    /// Like [`Almanac::route`], panicking with the categories that can be
+    /// reached if `to` can't.
+    fn expect_route(&self, from: &str, to: &str) -> Vec<&IdMapper<'i>> {
+        self.route(from, to).unwrap_or_else(|| {
+            let next = |category: &&str| self.next_categories(category);
+            let reached = bfs(from, next, |_| false);
+            panic!(
+                "no chain of maps from {from} to {to}; {from} only reaches {}",
+                reached.order().join(", ")
+            )
+        })
+    }
+}
+
+/// Something wrong with an [`Almanac`]. Lines are 1-based, as in
+/// diagnostics.
+#[derive(Debug, PartialEq, Eq)]
+enum Problem<'i> {
+    /// A second map between the same categories, on these lines. The first
+    /// one is used.
+    DuplicateMap(&'i str, usize, usize),
+    /// A range of length zero, on this line of this map.
+    EmptyRange(&'i str, usize),
+    /// A range whose source or destination runs past `usize::MAX`.
+    Overflow(&'i str, usize),
+    /// Two ranges of one map with overlapping sources. The first listed
+    /// wins.
+    Overlap(&'i str, usize, usize),
+    /// A seed list of this length can't be read as start/length pairs.
+    OddSeedList(usize),
+    /// A seed range, as start and length, running past `usize::MAX`.
+    SeedOverflow(usize, usize),
+    /// A seed range of length zero, starting here.
+    EmptySeedRange(usize),
+}
+impl std::fmt::Display for Problem<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Problem::DuplicateMap(map, a, b) => write!(f, "lines {a} and {b}: {map} given twice"),
+            Problem::EmptyRange(map, line) => write!(f, "line {line}: empty range in {map}"),
+            Problem::Overflow(map, line) => {
+                write!(f, "line {line}: range in {map} runs past {}", usize::MAX)
+            }
+            Problem::Overlap(map, a, b) => {
+                write!(f, "lines {a} and {b}: overlapping sources in {map}")
+            }
+            Problem::OddSeedList(len) => {
+                write!(f, "{len} seeds can't be read as start/length pairs")
+            }
+            Problem::SeedOverflow(start, len) => {
+                write!(f, "seed range {start} +{len} runs past {}", usize::MAX)
+            }
+            Problem::EmptySeedRange(start) => write!(f, "seed range {start} +0 is empty"),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct IdMapper<'i> {
-    _name: &'i str,
+    name: &'i str,
+    from: &'i str,
+    to: &'i str,
+    span: DocSpan,
     ranges: Vec<MapRange>,
 }
 impl<'i> IdMapper<'i> {
-    fn map_id(&self, id: usize) -> usize {
-        self.ranges
-            .iter()
-            .find_map(|range| range.map_id(id))
-            .unwrap_or(id)
+    /// This mapper as a [`PiecewiseMap`]. Where ranges overlap, the first
+    /// one listed wins.
+    fn to_piecewise(&self) -> PiecewiseMap {
+        let mut map = PiecewiseMap::identity();
+        for range in self.ranges.iter().rev() {
+            let (Some(src), Some(_)) = (range.sources(), range.destinations()) else {
+                continue;
+            };
+            let offset = range.dst as i128 - range.src as i128;
+            map.overlay(*src.start(), *src.end(), offset);
+        }
+        map
     }
-    fn map_rangeset(&self, mut original: RangeSetBlaze<usize>) -> RangeSetBlaze<usize> {
-        let mut new = RangeSetBlaze::new();
+}
+// The stage-by-stage inverses below are the reference the tests check
+// `PiecewiseMap::preimage` against; the solver only needs the composed map.
+#[cfg(test)]
+impl<'i> IdMapper<'i> {
+    /// Every id this mapper sends into `ids`: those moved there by a range,
+    /// and those in `ids` that no range covers.
+    fn preimage(&self, ids: RangeInclusive<usize>) -> RangeSetBlaze<usize> {
+        let mut covered = RangeSetBlaze::new();
+        let mut found = RangeSetBlaze::new();
         for range in &self.ranges {
-            new |= range.extract_mapped_range_from(&mut original);
+            let Some(src) = range.sources() else {
+                continue;
+            };
+            let src = RangeSetBlaze::from_iter([src]);
+            if let Some(moved) = range.preimage(ids.clone()) {
+                found |= &RangeSetBlaze::from_iter([moved]) - &covered;
+            }
+            covered |= src;
         }
-        new |= original;
-        new
+        found | (RangeSetBlaze::from_iter([ids]) - covered)
     }
 }
 #[derive(Debug)]
@@ -84,62 +310,402 @@ struct MapRange {
     len: usize,
 }
 impl MapRange {
-    fn map_id(&self, id: usize) -> Option<usize> {
-        (self.src..=(self.src + self.len - 1))
-            .contains(&id)
-            .then(|| id - self.src + self.dst)
-    }
-    fn extract_mapped_range_from(&self, range: &mut RangeSetBlaze<usize>) -> RangeSetBlaze<usize> {
-        let src = RangeSetBlaze::from_iter([self.src..=(self.src + self.len - 1)]);
-        let extracted = &*range & &src;
-        *range = &*range - src;
-        extracted
-            .into_ranges()
-            .map(|range| {
-                (range.start() - self.src + self.dst)..=(range.end() - self.src + self.dst)
+    /// The ids this range moves, or `None` if it's empty or runs past
+    /// `usize::MAX`.
+    fn sources(&self) -> Option<RangeInclusive<usize>> {
+        Some(self.src..=self.src.checked_add(self.len.checked_sub(1)?)?)
+    }
+    /// Where [`MapRange::sources`] end up.
+    fn destinations(&self) -> Option<RangeInclusive<usize>> {
+        Some(self.dst..=self.dst.checked_add(self.len.checked_sub(1)?)?)
+    }
+    /// The source ids this range moves into `ids`, if any.
+    #[cfg(test)]
+    fn preimage(&self, ids: RangeInclusive<usize>) -> Option<RangeInclusive<usize>> {
+        self.sources()?;
+        let dst = self.destinations()?;
+        let start = (*ids.start()).max(*dst.start());
+        let end = (*ids.end()).min(*dst.end());
+        (start <= end).then(|| start - self.dst + self.src..=end - self.dst + self.src)
+    }
+}
+
+/// Every seed the chain of mappers sends into `ids`, found by walking the
+/// chain backwards one mapper at a time.
+#[cfg(test)]
+fn preimage(mappers: &[&IdMapper], ids: RangeInclusive<usize>) -> RangeSetBlaze<usize> {
+    mappers
+        .iter()
+        .rev()
+        .fold(RangeSetBlaze::from_iter([ids]), |ids, mapper| {
+            ids.ranges()
+                .map(|ids| mapper.preimage(ids))
+                .fold(RangeSetBlaze::new(), |acc, set| acc | set)
+        })
+}
+
+/// Folds the whole chain of mappers into one map from seed to location.
+#[instrument(level = "debug", skip_all)]
+fn compose(mappers: &[&IdMapper]) -> PiecewiseMap {
+    let composed = mappers
+        .iter()
+        .fold(PiecewiseMap::identity(), |composed, mapper| {
+            let composed = composed.then(&mapper.to_piecewise());
+            debug!(
+                mapper = mapper.name,
+                span = %mapper.span,
+                pieces = composed.pieces.len(),
+                "composed stage"
+            );
+            composed
+        });
+    trace!("seed to location:\n{composed}");
+    composed
+}
+
+/// A map on ids that splits every `usize` into consecutive intervals and
+/// shifts each by its own offset. The pieces are kept sorted and merged, so
+/// lookups are a binary search.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct PiecewiseMap {
+    pieces: Vec<Piece>,
+}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Piece {
+    start: usize,
+    end: usize,
+    offset: i128,
+}
+impl Piece {
+    fn apply(&self, id: usize) -> usize {
+        usize::try_from(id as i128 + self.offset).expect("checked ranges stay within usize")
+    }
+    /// The id that [`Piece::apply`] sends to `id`.
+    fn unapply(&self, id: usize) -> usize {
+        usize::try_from(id as i128 - self.offset).expect("checked ranges stay within usize")
+    }
+}
+impl PiecewiseMap {
+    fn identity() -> Self {
+        PiecewiseMap {
+            pieces: vec![Piece {
+                start: 0,
+                end: usize::MAX,
+                offset: 0,
+            }],
+        }
+    }
+    /// Index of the piece holding `id`.
+    fn find(&self, id: usize) -> usize {
+        self.pieces.partition_point(|piece| piece.end < id)
+    }
+    fn map_id(&self, id: usize) -> usize {
+        self.pieces[self.find(id)].apply(id)
+    }
+    /// Where `ids` end up, as one range per piece they cross.
+    fn image(
+        &self,
+        ids: RangeInclusive<usize>,
+    ) -> impl Iterator<Item = RangeInclusive<usize>> + '_ {
+        let (start, end) = ids.into_inner();
+        self.pieces[self.find(start)..]
+            .iter()
+            .take_while(move |piece| piece.start <= end)
+            .map(move |piece| piece.apply(piece.start.max(start))..=piece.apply(piece.end.min(end)))
+    }
+    /// Every id that lands in `ids`.
+    fn preimage(&self, ids: RangeInclusive<usize>) -> RangeSetBlaze<usize> {
+        let (start, end) = ids.into_inner();
+        self.pieces
+            .iter()
+            .filter_map(|piece| {
+                let lo = piece.apply(piece.start).max(start);
+                let hi = piece.apply(piece.end).min(end);
+                (lo <= hi).then(|| piece.unapply(lo)..=piece.unapply(hi))
             })
             .collect()
     }
+    /// Shifts `start..=end` by `offset`, replacing whatever was there.
+    fn overlay(&mut self, start: usize, end: usize, offset: i128) {
+        let mut pieces = Vec::with_capacity(self.pieces.len() + 2);
+        for &piece in &self.pieces {
+            if piece.end < start || piece.start > end {
+                pieces.push(piece);
+                continue;
+            }
+            if piece.start < start {
+                pieces.push(Piece {
+                    end: start - 1,
+                    ..piece
+                });
+            }
+            if (piece.start..=piece.end).contains(&start) {
+                pieces.push(Piece { start, end, offset });
+            }
+            if piece.end > end {
+                pieces.push(Piece {
+                    start: end + 1,
+                    ..piece
+                });
+            }
+        }
+        self.pieces = pieces;
+        self.merge();
+    }
+    /// `self` followed by `next`.
+    fn then(&self, next: &PiecewiseMap) -> PiecewiseMap {
+        let mut pieces = Vec::new();
+        for piece in &self.pieces {
+            let (lo, hi) = (piece.apply(piece.start), piece.apply(piece.end));
+            for other in next.pieces[next.find(lo)..]
+                .iter()
+                .take_while(|other| other.start <= hi)
+            {
+                let (a, b) = (other.start.max(lo), other.end.min(hi));
+                pieces.push(Piece {
+                    start: piece.unapply(a),
+                    end: piece.unapply(b),
+                    offset: piece.offset + other.offset,
+                });
+            }
+        }
+        let mut composed = PiecewiseMap { pieces };
+        composed.merge();
+        composed
+    }
+    /// Joins neighbouring pieces with the same offset.
+    fn merge(&mut self) {
+        self.pieces.dedup_by(|next, prev| {
+            let joins = prev.offset == next.offset && prev.end.checked_add(1) == Some(next.start);
+            if joins {
+                prev.end = next.end;
+            }
+            joins
+        });
+    }
+}
+impl std::fmt::Display for PiecewiseMap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for piece in &self.pieces {
+            writeln!(
+                f,
+                "{}..={} -> {}..={} ({:+})",
+                piece.start,
+                piece.end,
+                piece.apply(piece.start),
+                piece.apply(piece.end),
+                piece.offset
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn composition() {
+    let almanac = parse_located_or_report(LineLocated::new(TEST_INPUT), input_p);
+    let mappers = almanac.expect_route("seed", "location");
+    let naive = |id: usize| {
+        mappers.iter().fold(id, |id, mapper| {
+            let range = mapper
+                .ranges
+                .iter()
+                .find(|r| (r.src..r.src + r.len).contains(&id));
+            range.map_or(id, |r| id - r.src + r.dst)
+        })
+    };
+    let almanac = compose(&mappers);
+    assert!(almanac
+        .pieces
+        .windows(2)
+        .all(|w| w[0].end + 1 == w[1].start));
+    for id in 0..=120 {
+        assert_eq!(almanac.map_id(id), naive(id), "seed {id}");
+    }
+    for (start, end) in [(0, 120), (79, 92), (55, 67), (98, 99)] {
+        let image = almanac.image(start..=end).flatten().sorted().collect_vec();
+        let expected = (start..=end).map(naive).sorted().collect_vec();
+        assert_eq!(image, expected);
+    }
+    assert_eq!(almanac.map_id(usize::MAX), usize::MAX);
+    assert_eq!(
+        PiecewiseMap::identity().then(&almanac),
+        almanac.then(&PiecewiseMap::identity())
+    );
+    assert_eq!(almanac.to_string().lines().count(), almanac.pieces.len());
+}
+#[test]
+fn inverse() {
+    let almanac = parse_located_or_report(LineLocated::new(TEST_INPUT), input_p);
+    let mappers = almanac.expect_route("seed", "location");
+    let almanac = compose(&mappers);
+    // A pseudo-random walk over ids, denser where the example's ranges are.
+    let ids = crate::bench::pseudo_random().map(|x| (x % 128) as usize);
+    for id in ids.take(300) {
+        let mapped = almanac.map_id(id);
+        assert!(almanac.preimage(mapped..=mapped).contains(id));
+        assert!(preimage(&mappers, mapped..=mapped).contains(id));
+        let mut stage = id;
+        for mapper in &mappers {
+            let next = mapper.to_piecewise().map_id(stage);
+            assert!(mapper.preimage(next..=next).contains(stage));
+            stage = next;
+        }
+        assert_eq!(stage, mapped);
+    }
+    let location = Day5.part2(TEST_INPUT);
+    assert_eq!(
+        almanac.preimage(location..=location),
+        preimage(&mappers, location..=location)
+    );
+    let parsed = parse_located_or_report(LineLocated::new(TEST_INPUT), input_p);
+    let closest = parsed.preimage("seed", "location", location..=location);
+    assert_eq!(closest, RangeSetBlaze::from_iter([82..=82]));
+    // Seed 82 is water 84 by the time it reaches location 46.
+    assert!(parsed
+        .preimage("water", "location", location..=location)
+        .contains(84));
+    assert_eq!(
+        parsed.preimage("soil", "soil", 7..=9),
+        RangeSetBlaze::from_iter([7..=9])
+    );
+    for (start, end) in [(0, 0), (35, 35), (40, 90), (0, 200)] {
+        let chain = preimage(&mappers, start..=end);
+        assert_eq!(chain, almanac.preimage(start..=end));
+        assert!(chain
+            .iter()
+            .all(|id| (start..=end).contains(&almanac.map_id(id))));
+    }
+    let range = MapRange {
+        dst: 50,
+        src: 98,
+        len: 2,
+    };
+    assert_eq!(range.preimage(0..=50), Some(98..=98));
+    assert_eq!(range.preimage(52..=60), None);
+}
+#[test]
+fn routes() {
+    let blocks = TEST_INPUT.split("\n\n").collect_vec();
+    // Same blocks, maps in reverse order.
+    let shuffled = [blocks[0]]
+        .into_iter()
+        .chain(blocks[1..].iter().rev().copied())
+        .join("\n\n");
+    assert_eq!(Day5.part1(&shuffled), 35);
+    assert_eq!(Day5.part2(&shuffled), 46);
+
+    let almanac = parse_located_or_report(LineLocated::new(&shuffled), input_p);
+    assert_eq!(almanac.map("seed", "humidity", [79, 14]), [78, 43]);
+    assert_eq!(almanac.map("soil", "light", [81]), [74]);
+    assert_eq!(almanac.map("seed", "seed", [5]), [5]);
+    assert!(almanac.route("location", "seed").is_none());
+    let route = almanac.route("water", "location").unwrap();
+    assert_eq!(
+        route.iter().map(|m| m.from).join(" "),
+        "water light temperature humidity"
+    );
+
+    let broken = TEST_INPUT.replace("light-to-temperature", "light-to-heat");
+    let result = std::panic::catch_unwind(|| Day5.part1(&broken));
+    let message = result.unwrap_err().downcast::<String>().unwrap();
+    assert!(
+        message.contains("seed only reaches seed, soil, fertilizer, water, light, heat"),
+        "{message}"
+    );
+}
+#[test]
+fn problems() {
+    let almanac = parse_located_or_report(LineLocated::new(TEST_INPUT), input_p);
+    assert_eq!(almanac.problems(true), []);
+
+    let broken = TEST_INPUT
+        .replace(
+            "seeds: 79 14 55 13",
+            "seeds: 79 14 55 18446744073709551615 30 0 7",
+        )
+        .replace("0 15 37\n", "0 15 37\n3 40 0\n")
+        .replace("88 18 7\n", "88 18 7\n18446744073709551610 0 10\n")
+        .replace("60 56 37\n", "60 56 37\n1 90 4\n");
+    let almanac = parse_located_or_report(LineLocated::new(&broken), input_p);
+    assert_eq!(almanac.seed_ranges().collect_vec(), [79..=92]);
+    assert_eq!(
+        almanac.problems(true),
+        [
+            Problem::OddSeedList(7),
+            Problem::SeedOverflow(55, usize::MAX),
+            Problem::EmptySeedRange(30),
+            Problem::EmptyRange("soil-to-fertilizer", 9),
+            Problem::Overflow("water-to-light", 21),
+            Problem::Overlap("humidity-to-location", 34, 35),
+            Problem::Overlap("humidity-to-location", 35, 36),
+        ]
+    );
+    assert_eq!(almanac.problems(false).len(), 4);
+    // Where sources overlap, the first range listed wins.
+    assert_eq!(almanac.map("humidity", "location", [91]), [95]);
+    let message = std::panic::catch_unwind(|| almanac.check(false))
+        .unwrap_err()
+        .downcast::<String>()
+        .unwrap();
+    assert!(message.starts_with("almanac has 4 problem(s):\n  line 9: empty range"));
+
+    // A map repeated at the end: reported, and the first copy is the one used.
+    let repeated = format!("{TEST_INPUT}\n\nseed-to-soil map:\n0 98 2");
+    let almanac = parse_located_or_report(LineLocated::new(&repeated), input_p);
+    assert_eq!(
+        almanac.problems(false),
+        [Problem::DuplicateMap("seed-to-soil", 3, 35)]
+    );
+    assert_eq!(almanac.map("seed", "soil", [98]), [50]);
+    assert_eq!(almanac.map("seed", "location", [79]), [82]);
 }
 
 // --------------- Parsing -----------------
 
-fn input_p<'i>(input: &mut &'i str) -> PResult<(Vec<usize>, Vec<IdMapper<'i>>)> {
-    (
-        seed_list_p,
-        multispace0,
-        separated(0.., idmapper_p, multispace1),
-    )
-        .map(|(seeds, _, mappers)| (seeds, mappers))
+#[instrument(level = "debug", skip_all)]
+fn input_p<'i>(input: &mut LineLocated<&'i str>) -> PResult<Almanac<'i>> {
+    separated_pair(seed_list_p, blank_lines_p, blocks_p(idmapper_p))
+        .map(|(seeds, mappers)| Almanac::new(seeds, mappers))
         .parse_next(input)
 }
 
-fn idmapper_p<'i>(input: &mut &'i str) -> PResult<IdMapper<'i>> {
-    (
-        take_till(0.., ' '),
-        (space1, "map:", multispace0),
-        separated(1.., maprange_p, multispace1),
-    )
-        .map(|(_name, _, ranges)| IdMapper { ranges, _name })
-        .parse_next(input)
+fn idmapper_p<'i>(input: &mut LineLocated<&'i str>) -> PResult<IdMapper<'i>> {
+    let name_p = separated_pair(alpha1, "-to-", alpha1)
+        .with_recognized()
+        .context(StrContext::Expected(StrContextValue::Description(
+            "source-to-destination",
+        )));
+    with_docspan((
+        name_p,
+        (space1, "map:", space0, line_ending),
+        lines_p(maprange_p),
+    ))
+    .map(|((((from, to), name), _, ranges), span)| IdMapper {
+        name,
+        from,
+        to,
+        span,
+        ranges,
+    })
+    .context(StrContext::Label("map"))
+    .parse_next(input)
 }
 
-fn maprange_p(input: &mut &str) -> PResult<MapRange> {
-    separated(3, digit1.parse_to::<usize>(), space1)
-        .map(|ns: Vec<_>| MapRange {
-            dst: ns[0],
-            src: ns[1],
-            len: ns[2],
-        })
+fn maprange_p(input: &mut LineLocated<&str>) -> PResult<MapRange> {
+    uints_p
+        .verify_map(|ns: Vec<usize>| <[usize; 3]>::try_from(ns).ok())
+        .context(StrContext::Expected(StrContextValue::Description(
+            "destination, source and length",
+        )))
+        .map(|[dst, src, len]| MapRange { dst, src, len })
         .parse_next(input)
 }
 
-fn seed_list_p(input: &mut &str) -> PResult<Vec<usize>> {
-    preceded(
-        ("seeds:", space0),
-        separated(1.., digit1.parse_to::<usize>(), space1),
-    )
-    .parse_next(input)
+fn seed_list_p(input: &mut LineLocated<&str>) -> PResult<Vec<usize>> {
+    labelled_p("seeds", uints_p)
+        .context(StrContext::Label("seed list"))
+        .parse_next(input)
 }
 
 #[cfg(test)]
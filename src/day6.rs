@@ -1,12 +1,16 @@
-use crate::AocDay;
+use crate::{
+    linespan::parse_or_report,
+    parse::{labelled_p, uints_p},
+    AocDay,
+};
 
 use itertools::izip;
-use winnow::{
-    ascii::{digit1, multispace0, space0, space1},
-    combinator::{preceded, separated},
-    prelude::*,
-    token::take_till,
+use std::{
+    fmt::{Debug, Display},
+    ops::{Add, Div, RangeInclusive, Sub},
 };
+use tracing::instrument;
+use winnow::{ascii::multispace0, prelude::*};
 
 pub struct Day6;
 impl AocDay for Day6 {
@@ -14,24 +18,41 @@ impl AocDay for Day6 {
         include_str!("data/day6.txt")
     }
 
-    fn part1(&self, mut input: &str) -> usize {
-        let races = races_p(&mut input).unwrap();
-        races.into_iter().map(|r| r.num_wins()).product()
+    fn part1(&self, input: &str) -> usize {
+        let races = parse_or_report(input, races_p);
+        races
+            .into_iter()
+            .map(|r| usize::try_from(r.num_wins()).unwrap())
+            .product()
     }
 
-    fn part2(&self, mut input: &str) -> usize {
-        let races = races_p(&mut input).unwrap();
-        let mut time_s = String::new();
-        let mut dist_s = String::new();
-        for Race { time, dist } in races {
-            time_s += &time.to_string();
-            dist_s += &dist.to_string();
-        }
-        let race = Race {
-            time: time_s.parse().unwrap(),
-            dist: dist_s.parse().unwrap(),
-        };
-        race.num_wins()
+    fn part2(&self, input: &str) -> usize {
+        let races = parse_or_report(input, races_p);
+        usize::try_from(joined(&races).num_wins()).unwrap()
+    }
+
+    fn analysis(&self, input: &str) -> Option<String> {
+        let races = parse_or_report(input, races_p);
+        Some(format!(
+            "{}\n{}",
+            Margins(&races),
+            Margins(&[joined(&races)])
+        ))
+    }
+}
+
+/// The single race part 2 reads the sheet as, ignoring the spaces.
+/// Concatenated digits can outgrow a u64.
+fn joined(races: &[Race<u64>]) -> Race<u128> {
+    let mut time_s = String::new();
+    let mut dist_s = String::new();
+    for Race { time, dist } in races {
+        time_s += &time.to_string();
+        dist_s += &dist.to_string();
+    }
+    Race {
+        time: time_s.parse().unwrap(),
+        dist: dist_s.parse().unwrap(),
     }
 }
 
@@ -45,40 +66,261 @@ fn part2() {
     assert_eq!(Day6.part2(TEST_INPUT), 71503)
 }
 
+/// Unsigned integers a race can be measured in.
+trait Uint:
+    Copy + Ord + Debug + Display + Add<Output = Self> + Sub<Output = Self> + Div<Output = Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+    const TWO: Self;
+    fn isqrt(self) -> Self;
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+}
+macro_rules! impl_uint {
+    ($($t:ty),*) => {$(
+        impl Uint for $t {
+            const ZERO: Self = 0;
+            const ONE: Self = 1;
+            const TWO: Self = 2;
+            fn isqrt(self) -> Self {
+                <$t>::isqrt(self)
+            }
+            fn checked_mul(self, rhs: Self) -> Option<Self> {
+                <$t>::checked_mul(self, rhs)
+            }
+        }
+    )*};
+}
+impl_uint!(u64, u128);
+
 #[derive(Debug)]
-struct Race {
-    time: usize,
-    dist: usize,
+struct Race<T> {
+    time: T,
+    dist: T,
 }
-impl Race {
-    fn num_wins(&self) -> usize {
-        let t = self.time as f64;
-        let d = self.dist as f64;
-        let desc = (t.powf(2.) - 4. * d).sqrt();
-        let highest = ((t + desc) / 2.).ceil() as usize - 1;
-        let lowest = ((t - desc) / 2.).floor() as usize + 1;
-        highest - lowest + 1
+impl<T: Uint> Race<T> {
+    /// Whether holding for `hold` beats the record. Divides rather than
+    /// multiplies, so it can't overflow.
+    fn beats(&self, hold: T) -> bool {
+        hold > T::ZERO && hold <= self.time && self.time - hold > self.dist / hold
+    }
+
+    /// The shortest winning hold. Distances are symmetric about `time / 2`,
+    /// where they peak, so there is none if holding that long loses.
+    fn lowest_win(&self) -> Option<T> {
+        let mid = self.time / T::TWO;
+        if !self.beats(mid) {
+            return None;
+        }
+        let mut hold = self.root_estimate().unwrap_or_else(|| self.bisect(mid));
+        while !self.beats(hold) {
+            hold = hold + T::ONE;
+        }
+        while self.beats(hold - T::ONE) {
+            hold = hold - T::ONE;
+        }
+        Some(hold)
+    }
+
+    /// The lower root of `hold * (time - hold) = dist`, rounded down, or
+    /// `None` if `time * time` overflows.
+    fn root_estimate(&self) -> Option<T> {
+        let square = self.time.checked_mul(self.time)?;
+        let four_d = self.dist.checked_mul(T::TWO + T::TWO)?;
+        let disc = square - four_d.min(square);
+        Some((self.time - disc.isqrt()) / T::TWO)
+    }
+
+    /// The lowest winning hold in `1..=mid`, given that `mid` wins.
+    fn bisect(&self, mid: T) -> T {
+        let (mut lo, mut hi) = (T::ZERO, mid);
+        while hi - lo > T::ONE {
+            let m = lo + (hi - lo) / T::TWO;
+            if self.beats(m) {
+                hi = m;
+            } else {
+                lo = m;
+            }
+        }
+        hi
+    }
+
+    /// Every winning hold, empty if the record can't be beaten.
+    fn wins(&self) -> Option<RangeInclusive<T>> {
+        let lowest = self.lowest_win()?;
+        Some(lowest..=self.time - lowest)
+    }
+
+    fn num_wins(&self) -> T {
+        match self.wins() {
+            Some(wins) => *wins.end() - *wins.start() + T::ONE,
+            None => T::ZERO,
+        }
+    }
+
+    /// How far the boat goes after holding for `hold`, or `None` if that
+    /// doesn't fit in `T`.
+    fn distance(&self, hold: T) -> Option<T> {
+        hold.checked_mul(self.time - hold.min(self.time))
+    }
+
+    /// The hold that goes furthest, and how far it goes. Odd times have two
+    /// such holds; this is the shorter.
+    fn optimal(&self) -> (T, Option<T>) {
+        let hold = self.time / T::TWO;
+        (hold, self.distance(hold))
+    }
+}
+
+/// A table of each race's winning holds and how far the best one beats the
+/// record by.
+struct Margins<'r, T>(&'r [Race<T>]);
+impl<T: Uint> Display for Margins<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let show = |value: Option<T>| value.map_or("-".to_string(), |v| v.to_string());
+        let mut rows = vec![[
+            "time", "record", "wins", "holds", "best", "distance", "margin",
+        ]
+        .map(String::from)];
+        for race in self.0 {
+            let (best, distance) = race.optimal();
+            let holds = race
+                .wins()
+                .map_or("-".to_string(), |w| format!("{}..={}", w.start(), w.end()));
+            let margin = distance.filter(|&d| d > race.dist).map(|d| d - race.dist);
+            rows.push([
+                race.time.to_string(),
+                race.dist.to_string(),
+                race.num_wins().to_string(),
+                holds,
+                best.to_string(),
+                show(distance),
+                show(margin),
+            ]);
+        }
+        let widths = (0..7).map(|c| rows.iter().map(|r| r[c].len()).max().unwrap());
+        let widths = widths.collect::<Vec<_>>();
+        for row in rows {
+            let cells = row
+                .iter()
+                .zip(&widths)
+                .map(|(cell, &w)| format!("{cell:>w$}"));
+            writeln!(f, "{}", cells.collect::<Vec<_>>().join("  "))?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn analysis() {
+    let races = parse_or_report(TEST_INPUT, races_p);
+    let race = &races[1];
+    assert_eq!(race.wins(), Some(4..=11));
+    assert_eq!(race.optimal(), (7, Some(56)));
+    assert_eq!(
+        Race {
+            time: 4u64,
+            dist: 4
+        }
+        .wins(),
+        None
+    );
+    assert_eq!(
+        Race {
+            time: u64::MAX,
+            dist: 0
+        }
+        .optimal()
+        .1,
+        None
+    );
+    assert_eq!(
+        Margins(&races).to_string(),
+        "time  record  wins    holds  best  distance  margin\n\
+         \x20  7       9     4    2..=5     3        12       3\n\
+         \x20 15      40     8   4..=11     7        56      16\n\
+         \x20 30     200     9  11..=19    15       225      25\n"
+    );
+    let race = joined(&races);
+    assert_eq!(race.wins(), Some(14..=71516));
+    assert_eq!(race.optimal(), (35765, Some(35765 * 35765)));
+}
+
+#[test]
+fn exact_wins() {
+    fn brute(time: u64, dist: u64) -> u64 {
+        (0..=time).filter(|h| h * (time - h) > dist).count() as u64
+    }
+    for time in 0..64u64 {
+        for dist in 0..=time * time / 4 + 2 {
+            let wins = brute(time, dist);
+            assert_eq!(Race { time, dist }.num_wins(), wins, "{time} {dist}");
+            let (time, dist) = (time as u128, dist as u128);
+            assert_eq!(Race { time, dist }.num_wins(), wins as u128);
+        }
+    }
+
+    // Past 2^53, where f64 can't tell neighbouring holds apart. The first
+    // race overflows `time * time` in a u64; the second doesn't in a u128.
+    for hold in [1, 12_345, 1 << 20] {
+        let time = (1u64 << 40) + 3;
+        let dist = hold * (time - hold) - 1;
+        assert_eq!(Race { time, dist }.num_wins(), time - 2 * hold + 1);
+        assert_eq!(
+            Race {
+                time,
+                dist: dist + 1
+            }
+            .num_wins(),
+            time - 2 * hold - 1
+        );
+    }
+    for hold in [1, (1 << 58) + 12_345, (1 << 59) - 1] {
+        let time = (1u128 << 60) + 1;
+        let dist = hold * (time - hold) - 1;
+        assert_eq!(Race { time, dist }.num_wins(), time - 2 * hold + 1);
+    }
+
+    let time = 1u128 << 100;
+    assert_eq!(Race { time, dist: 0 }.num_wins(), time - 1);
+    assert_eq!(
+        Race {
+            time,
+            dist: time - 1
+        }
+        .num_wins(),
+        time - 3
+    );
+    assert_eq!(
+        Race {
+            time: 1u64 << 32,
+            dist: 1 << 62
+        }
+        .num_wins(),
+        0
+    );
+    // Bisecting in a u64 agrees with the square root in a u128.
+    for dist in [0, 1 << 40, u64::MAX / 3, u64::MAX] {
+        let wins = Race {
+            time: u64::MAX,
+            dist,
+        }
+        .num_wins();
+        let (time, dist) = (u64::MAX as u128, dist as u128);
+        assert_eq!(wins as u128, Race { time, dist }.num_wins());
     }
 }
 
-fn races_p(input: &mut &str) -> PResult<Vec<Race>> {
-    let times = line_p(input)?;
+#[instrument(level = "debug", skip_all)]
+fn races_p(input: &mut &str) -> PResult<Vec<Race<u64>>> {
+    let times = labelled_p("Time", uints_p).parse_next(input)?;
     multispace0(input)?;
-    let dists = line_p(input)?;
+    let dists = labelled_p("Distance", uints_p).parse_next(input)?;
     Ok(izip!(times, dists)
-        .into_iter()
         .map(|(time, dist)| Race { time, dist })
         .collect())
 }
 
-fn line_p(input: &mut &str) -> PResult<Vec<usize>> {
-    preceded(
-        (take_till(1.., ':'), ':', space0),
-        separated(1.., digit1.parse_to::<usize>(), space1),
-    )
-    .parse_next(input)
-}
-
 #[cfg(test)]
 const TEST_INPUT: &str = r#"Time:      7  15   30
 Distance:  9  40  200"#;
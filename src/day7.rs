@@ -1,9 +1,16 @@
-use crate::AocDay;
+use std::io::BufRead;
+
+use crate::{
+    linespan::{unlocated, LineLocated, LineReader},
+    AocDay, StreamingDay,
+};
 
 use itertools::Itertools;
+use tracing::instrument;
 use winnow::{
     ascii::{digit1, space1},
     combinator::repeat,
+    error::{StrContext, StrContextValue},
     prelude::*,
     token::any,
 };
@@ -15,24 +22,37 @@ impl AocDay for Day7 {
     }
 
     fn part1(&self, input: &str) -> usize {
-        input_p::<'S'>(input)
-            .into_iter()
-            .sorted()
-            .enumerate()
-            .map(|(rank, hand)| (rank + 1) * hand.bid)
-            .sum()
+        self.part1_stream(&mut input.as_bytes())
     }
 
     fn part2(&self, input: &str) -> usize {
-        input_p::<'J'>(input)
-            .into_iter()
-            .sorted()
-            .enumerate()
-            .map(|(rank, hand)| (rank + 1) * hand.bid)
-            .sum()
+        self.part2_stream(&mut input.as_bytes())
+    }
+
+    fn streaming(&self) -> Option<&dyn StreamingDay> {
+        Some(self)
+    }
+}
+impl StreamingDay for Day7 {
+    fn part1_stream(&self, input: &mut dyn BufRead) -> usize {
+        winnings(input, &Standard)
+    }
+
+    fn part2_stream(&self, input: &mut dyn BufRead) -> usize {
+        winnings(input, &JOKERS)
     }
 }
 
+/// Ranks every hand under `rules` and sums each bid times its rank.
+fn winnings(input: &mut dyn BufRead, rules: &dyn Rules) -> usize {
+    input_p(input, rules)
+        .into_iter()
+        .sorted()
+        .enumerate()
+        .map(|(rank, hand)| (rank + 1) * hand.bid)
+        .sum()
+}
+
 #[test]
 fn part1() {
     assert_eq!(Day7.part1(TEST_INPUT), 6440)
@@ -43,31 +63,25 @@ fn part2() {
     assert_eq!(Day7.part2(TEST_INPUT), 5905)
 }
 
-#[derive(Debug, Eq, Ord)]
-struct Hand<const D: DeckType> {
-    cards: [Card<D>; 5],
-    bid: usize,
-}
-impl<const D: DeckType> Hand<D> {
-    fn hand_type(&self) -> HandType {
-        let mut groups = self
-            .cards
-            .iter()
-            .sorted()
-            .group_by(|c| *c)
-            .into_iter()
-            .map(|group| group.1.count())
-            .collect_vec();
-        let num_jokers = self
-            .cards
-            .contains(&Card::Joker)
-            .then(|| groups.remove(0))
-            .unwrap_or_default();
+/// How a game of Camel Cards is scored: the order cards break ties in, and
+/// which cards stand in for whatever makes the hand strongest.
+trait Rules {
+    /// Tie-breaking strength of `card`; higher beats lower.
+    fn rank(&self, card: Card) -> u8;
+
+    fn is_wild(&self, card: Card) -> bool;
+
+    /// Wild cards join the largest group of the rest, which always gives
+    /// the strongest hand.
+    fn classify(&self, cards: &[Card; 5]) -> HandType {
+        let wild = cards.iter().filter(|&&c| self.is_wild(c)).count();
+        let rest = cards.iter().filter(|&&c| !self.is_wild(c));
+        let mut groups = rest.counts().into_values().collect_vec();
         groups.sort();
-        if groups.is_empty() {
-            groups.push(0);
+        match groups.last_mut() {
+            Some(largest) => *largest += wild,
+            None => groups.push(wild),
         }
-        *groups.last_mut().unwrap() += num_jokers;
         match groups.as_slice() {
             [5] => HandType::FiveOfAKind,
             [1, 4] => HandType::FourOfAKind,
@@ -80,17 +94,174 @@ impl<const D: DeckType> Hand<D> {
         }
     }
 }
-impl<const D: DeckType> PartialEq for Hand<D> {
+
+/// Part 1: cards rank by face value and none are wild.
+struct Standard;
+impl Rules for Standard {
+    fn rank(&self, card: Card) -> u8 {
+        card as u8
+    }
+
+    fn is_wild(&self, _card: Card) -> bool {
+        false
+    }
+}
+
+/// One face is wild, ranked below every other card or, if `high`, above.
+struct Wildcard {
+    card: Card,
+    high: bool,
+}
+impl Rules for Wildcard {
+    fn rank(&self, card: Card) -> u8 {
+        match (card == self.card, self.high) {
+            (true, false) => 0,
+            (true, true) => Card::A as u8 + 2,
+            (false, _) => card as u8 + 1,
+        }
+    }
+
+    fn is_wild(&self, card: Card) -> bool {
+        card == self.card
+    }
+}
+
+/// Part 2: jacks are jokers, wild but the weakest card on a tie.
+const JOKERS: Wildcard = Wildcard {
+    card: Card::J,
+    high: false,
+};
+
+#[test]
+fn rules() {
+    let cards = Card::ALL.iter().copied();
+    let wilds = [
+        JOKERS,
+        Wildcard {
+            card: Card::Q,
+            high: true,
+        },
+    ];
+    for rules in &wilds {
+        let ranks = cards.clone().map(|c| rules.rank(c)).collect_vec();
+        assert!(ranks.iter().all_unique());
+        assert_eq!(
+            ranks.iter().position_max(),
+            Some(if rules.high { 10 } else { 12 })
+        );
+    }
+    // Every wild card replaced by every face: the best must match.
+    for hand in [
+        "JJJJ2", "QJJQ2", "T55J5", "KTJJT", "2345J", "QQQJA", "J2Q2Q",
+    ] {
+        let hand: [Card; 5] = hand
+            .chars()
+            .map(|c| c.try_into().unwrap())
+            .collect_vec()
+            .try_into()
+            .unwrap();
+        for rules in &wilds {
+            let best = (0..5)
+                .map(|i| {
+                    let faces = cards.clone().filter(|&c| !rules.is_wild(c)).collect_vec();
+                    if rules.is_wild(hand[i]) {
+                        faces
+                    } else {
+                        vec![hand[i]]
+                    }
+                })
+                .multi_cartesian_product()
+                .map(|h| Standard.classify(&h.try_into().unwrap()))
+                .max();
+            assert_eq!(Some(rules.classify(&hand)), best, "{hand:?}");
+        }
+    }
+    // Four of a kind both, so the tie comes down to the joker against the
+    // queen.
+    let high = Wildcard {
+        card: Card::J,
+        high: true,
+    };
+    let hands = "JKKK2 1\nQQQQ2 2";
+    assert_eq!(winnings(&mut hands.as_bytes(), &JOKERS), 1 + 2 * 2);
+    assert_eq!(winnings(&mut hands.as_bytes(), &high), 2 + 2);
+}
+
+/// A hand scored once, when dealt, under the rules of the game. Hands
+/// compare by `key` alone, so two hands that score the same are equal
+/// whatever their bids.
+#[derive(Debug)]
+struct Hand {
+    key: Key,
+    bid: usize,
+}
+impl Hand {
+    fn new(cards: [Card; 5], bid: usize, rules: &dyn Rules) -> Self {
+        let key = Key {
+            hand_type: rules.classify(&cards),
+            ranks: cards.map(|c| rules.rank(c)),
+        };
+        Hand { key, bid }
+    }
+}
+impl PartialEq for Hand {
     fn eq(&self, other: &Self) -> bool {
-        self.cards == other.cards
+        self.key == other.key
     }
 }
-impl<const D: DeckType> PartialOrd for Hand<D> {
+impl Eq for Hand {}
+impl PartialOrd for Hand {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        match self.hand_type().partial_cmp(&other.hand_type()) {
-            Some(core::cmp::Ordering::Equal) => self.cards.partial_cmp(&other.cards),
-            ord => ord,
+        Some(self.cmp(other))
+    }
+}
+impl Ord for Hand {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+/// The type of hand, then the ranks of its cards in the order dealt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Key {
+    hand_type: HandType,
+    ranks: [u8; 5],
+}
+
+#[test]
+fn ordering() {
+    use std::cmp::Ordering;
+
+    // Every hand of twos, jacks, queens and aces, under each set of rules.
+    let faces = [Card::N2, Card::J, Card::Q, Card::A];
+    let cards = (0..5).map(|_| faces).multi_cartesian_product();
+    let cards = cards
+        .map(|h| <[Card; 5]>::try_from(h).unwrap())
+        .collect_vec();
+    let high = Wildcard {
+        card: Card::Q,
+        high: true,
+    };
+    for rules in [&Standard as &dyn Rules, &JOKERS, &high] {
+        let hands = cards
+            .iter()
+            .enumerate()
+            .map(|(bid, &c)| Hand::new(c, bid, rules))
+            .sorted()
+            .collect_vec();
+        for (i, a) in hands.iter().enumerate() {
+            assert_eq!(a.cmp(a), Ordering::Equal);
+            for (j, b) in hands.iter().enumerate() {
+                let ord = a.cmp(b);
+                assert_eq!(a.partial_cmp(b), Some(ord));
+                assert_eq!(b.cmp(a), ord.reverse());
+                assert_eq!(a == b, ord == Ordering::Equal);
+                // Sorted, so anything later is no smaller.
+                assert!(i.cmp(&j) == ord || ord == Ordering::Equal);
+            }
         }
+        // Ranks are distinct, so only identical cards tie.
+        assert!(hands.windows(2).all(|w| w[0] < w[1]));
     }
 }
 
@@ -105,11 +276,9 @@ enum HandType {
     FiveOfAKind,
 }
 
-type DeckType = char;
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
-enum Card<const D: DeckType> {
-    Joker,
+/// A card's face. How it ranks and whether it is wild are up to the `Rules`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Card {
     N2,
     N3,
     N4,
@@ -124,7 +293,25 @@ enum Card<const D: DeckType> {
     K,
     A,
 }
-impl<const D: DeckType> TryFrom<char> for Card<D> {
+impl Card {
+    #[cfg(test)]
+    const ALL: [Card; 13] = [
+        Card::N2,
+        Card::N3,
+        Card::N4,
+        Card::N5,
+        Card::N6,
+        Card::N7,
+        Card::N8,
+        Card::N9,
+        Card::N10,
+        Card::J,
+        Card::Q,
+        Card::K,
+        Card::A,
+    ];
+}
+impl TryFrom<char> for Card {
     type Error = ();
 
     fn try_from(value: char) -> Result<Self, Self::Error> {
@@ -138,11 +325,7 @@ impl<const D: DeckType> TryFrom<char> for Card<D> {
             '8' => Ok(Card::N8),
             '9' => Ok(Card::N9),
             'T' => Ok(Card::N10),
-            'J' => Ok(match D {
-                'S' => Card::J,
-                'J' => Card::Joker,
-                _ => unimplemented!(),
-            }),
+            'J' => Ok(Card::J),
             'Q' => Ok(Card::Q),
             'K' => Ok(Card::K),
             'A' => Ok(Card::A),
@@ -151,25 +334,34 @@ impl<const D: DeckType> TryFrom<char> for Card<D> {
     }
 }
 
-fn input_p<const D: DeckType>(input: &str) -> Vec<Hand<D>> {
-    input
-        .lines()
-        .map(|mut line| hand_p(&mut line).unwrap())
-        .collect()
+#[instrument(level = "debug", skip_all)]
+/// Ranking needs every hand, so this holds the parsed hands (not the text).
+fn input_p(input: &mut dyn BufRead, rules: &dyn Rules) -> Vec<Hand> {
+    LineReader::new(input).parse_all_or_report(|input: &mut LineLocated<&str>| {
+        let (cards, bid) = unlocated(hand_p).parse_next(input)?;
+        Ok(Hand::new(cards, bid, rules))
+    })
 }
 
-fn hand_p<const D: DeckType>(input: &mut &str) -> PResult<Hand<D>> {
-    let cards: [Card<D>; 5] = repeat::<_, _, Vec<_>, _, _>(5, card_p)
+fn hand_p(input: &mut &str) -> PResult<([Card; 5], usize)> {
+    let cards: [Card; 5] = repeat::<_, _, Vec<_>, _, _>(5, card_p)
+        .context(StrContext::Label("hand"))
         .parse_next(input)?
         .try_into()
         .unwrap();
     let _ = space1.parse_next(input)?;
-    let bid = digit1.parse_to::<usize>().parse_next(input)?;
-    Ok(Hand { cards, bid })
+    let bid = digit1
+        .parse_to::<usize>()
+        .context(StrContext::Label("bid"))
+        .parse_next(input)?;
+    Ok((cards, bid))
 }
 
-fn card_p<const D: DeckType>(input: &mut &str) -> PResult<Card<D>> {
+fn card_p(input: &mut &str) -> PResult<Card> {
     any.verify_map(|c: char| c.try_into().ok())
+        .context(StrContext::Expected(StrContextValue::Description(
+            "card (2-9, T, J, Q, K or A)",
+        )))
         .parse_next(input)
 }
 
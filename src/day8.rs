@@ -1,9 +1,22 @@
-use std::{cmp::Ordering, collections::HashMap};
+use std::collections::HashMap;
 
-use crate::AocDay;
+use crate::{
+    cycle::find_cycle,
+    linespan::parse_or_report,
+    math::lcm,
+    parse::{blank_lines_p, lines_p},
+    AocDay,
+};
 
 use itertools::Itertools;
-use winnow::{ascii::space0, prelude::*, token::take};
+use tracing::{debug, instrument};
+use winnow::{
+    ascii::space0,
+    combinator::separated_pair,
+    error::StrContext,
+    prelude::*,
+    token::{take, take_while},
+};
 
 pub struct Day8;
 impl AocDay for Day8 {
@@ -14,7 +27,7 @@ impl AocDay for Day8 {
     fn part1(&self, input: &str) -> usize {
         let (lrs, nodemap) = input_p(input);
         let mut node = "AAA";
-        for (i, lr) in lrs.chars().into_iter().cycle().enumerate() {
+        for (i, lr) in lrs.chars().cycle().enumerate() {
             if node == "ZZZ" {
                 return i;
             }
@@ -31,19 +44,25 @@ impl AocDay for Day8 {
             .copied()
             .filter(|k| k.ends_with('A'))
             .collect_vec();
+        let lrs = lrs.as_bytes();
+        let step =
+            |&(node, i): &(&str, usize)| (nodemap.next(node, lrs[i] as char), (i + 1) % lrs.len());
         let mut cycles = Vec::new();
-        for mut node in nodes {
-            let mut seen = HashMap::<(&str, usize), usize>::new();
-            for (i, (lri, lr)) in lrs.chars().into_iter().enumerate().cycle().enumerate() {
-                if let Some(n) = seen.get(&(node, lri)) {
-                    cycles.push(i - n);
-                    break;
-                }
-                seen.insert((node, lri), i);
-                node = nodemap.next(node, lr);
-            }
+        for start in nodes {
+            let (cycle, _) = find_cycle((start, 0), step, |(node, _)| node.ends_with('Z'));
+            debug!(
+                start,
+                offset = cycle.tail,
+                length = cycle.length,
+                ends = ?cycle.hits,
+                "found cycle"
+            );
+            cycles.push(cycle.length as u64);
         }
-        cycles.into_iter().fold(1, lcm)
+        cycles
+            .into_iter()
+            .try_fold(1, lcm)
+            .expect("cycle lengths have an lcm that fits in a u64") as usize
     }
 }
 
@@ -53,20 +72,20 @@ fn part1() {
 }
 
 #[test]
-fn part2() {
-    assert_eq!(Day8.part2(TEST_INPUT_2), 6)
-}
-
-fn lcm(a: usize, b: usize) -> usize {
-    a * b / gcd(a, b)
+fn part1_search() {
+    let (lrs, nodemap) = input_p(TEST_INPUT_1);
+    let lrs = lrs.as_bytes();
+    let step =
+        |&(node, i): &(&str, usize)| [(nodemap.next(node, lrs[i] as char), (i + 1) % lrs.len())];
+    let reached = crate::search::bfs(("AAA", 0), step, |&(node, _)| node == "ZZZ");
+    let found = reached.found().unwrap();
+    assert_eq!(reached.cost(found), Some(6));
+    assert_eq!(reached.path_to(found).unwrap().len(), 7);
 }
 
-fn gcd(a: usize, b: usize) -> usize {
-    match a.cmp(&b) {
-        Ordering::Less => gcd(a, b - a),
-        Ordering::Equal => return a,
-        Ordering::Greater => gcd(a - b, b),
-    }
+#[test]
+fn part2() {
+    assert_eq!(Day8.part2(TEST_INPUT_2), 6)
 }
 
 #[derive(Debug)]
@@ -91,14 +110,12 @@ impl<'i> FromIterator<(&'i str, (&'i str, &'i str))> for NodeMap<'i> {
     }
 }
 
-fn input_p<'i>(input: &'i str) -> (&str, NodeMap<'i>) {
-    let mut lines = input.lines();
-    let lrs = lines.next().unwrap();
-    let nodemap = lines
-        .filter(|line| !line.is_empty())
-        .map(|mut line| node_p(&mut line).unwrap())
-        .collect();
-    (lrs, nodemap)
+#[instrument(level = "debug", skip_all)]
+fn input_p(input: &str) -> (&str, NodeMap<'_>) {
+    let lrs_p = take_while(1.., ['L', 'R']).context(StrContext::Label("directions"));
+    let nodes_p = lines_p(node_p.context(StrContext::Label("node")))
+        .map(|nodes: Vec<_>| nodes.into_iter().collect());
+    parse_or_report(input, separated_pair(lrs_p, blank_lines_p, nodes_p))
 }
 
 fn node_p<'i>(input: &mut &'i str) -> PResult<(&'i str, (&'i str, &'i str))> {
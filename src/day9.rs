@@ -1,13 +1,17 @@
-use std::ops::{Add, Sub};
+use std::{
+    io::BufRead,
+    ops::{Add, Sub},
+};
 
-use crate::AocDay;
+use crate::{
+    linespan::{unlocated, LineReader, ParseReport},
+    parse::ints_p,
+    AocDay, StreamingDay,
+};
 
 use itertools::Itertools;
-use winnow::{
-    ascii::{dec_int, space1},
-    combinator::separated,
-    prelude::*,
-};
+use tracing::instrument;
+use winnow::{error::StrContext, prelude::*};
 
 pub struct Day9;
 impl AocDay for Day9 {
@@ -16,18 +20,37 @@ impl AocDay for Day9 {
     }
 
     fn part1(&self, input: &str) -> usize {
-        input_p(input)
-            .into_iter()
-            .map(|seq| Extrapol(seq.as_slice()).next())
-            .sum::<i64>() as usize
+        self.part1_stream(&mut input.as_bytes())
     }
 
     fn part2(&self, input: &str) -> usize {
-        input_p(input)
-            .into_iter()
+        self.part2_stream(&mut input.as_bytes())
+    }
+
+    fn streaming(&self) -> Option<&dyn StreamingDay> {
+        Some(self)
+    }
+}
+impl StreamingDay for Day9 {
+    fn part1_stream(&self, input: &mut dyn BufRead) -> usize {
+        let mut failures = ParseReport::default();
+        let sum = LineReader::new(input)
+            .parse_each(unlocated(line_p), &mut failures)
+            .map(|seq| Extrapol(seq.as_slice()).next())
+            .sum::<i64>();
+        failures.finish();
+        sum as usize
+    }
+
+    fn part2_stream(&self, input: &mut dyn BufRead) -> usize {
+        let mut failures = ParseReport::default();
+        let sum = LineReader::new(input)
+            .parse_each(unlocated(line_p), &mut failures)
             .map(|seq| seq.into_iter().rev().collect_vec())
             .map(|seq| Extrapol(seq.as_slice()).next())
-            .sum::<i64>() as usize
+            .sum::<i64>();
+        failures.finish();
+        sum as usize
     }
 }
 
@@ -65,16 +88,11 @@ impl<'a, T: Copy + PartialEq + Add<Output = T> + Sub<Output = T>> Extrapol<'a, T
     }
 }
 
-fn input_p(input: &str) -> Vec<Vec<i64>> {
-    input
-        .lines()
-        .into_iter()
-        .map(|mut line| line_p(&mut line).unwrap())
-        .collect()
-}
-
+#[instrument(level = "trace", skip_all)]
 fn line_p(input: &mut &str) -> PResult<Vec<i64>> {
-    separated(1.., dec_int::<_, i64, _>, space1).parse_next(input)
+    ints_p
+        .context(StrContext::Label("sequence"))
+        .parse_next(input)
 }
 
 #[cfg(test)]
@@ -0,0 +1,746 @@
+// A toolkit for grid puzzles; Day3 and Day10 use only part of it.
+#![allow(dead_code)]
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Display,
+    hash::{DefaultHasher, Hash, Hasher},
+    ops::{Index, IndexMut},
+};
+
+use num_enum::TryFromPrimitive;
+#[cfg(test)]
+use {crate::parse::grid_p, winnow::Parser};
+
+/// A cell position as `(x, y)`, with `y` growing downwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Coord(pub usize, pub usize);
+impl Coord {
+    /// The neighbouring coordinate in `dir`, or `None` below zero.
+    pub fn step(self, dir: Dir) -> Option<Coord> {
+        let (dx, dy) = dir.delta();
+        self.offset(dx, dy)
+    }
+    /// Moves by a signed delta, or `None` below zero.
+    pub fn offset(self, dx: isize, dy: isize) -> Option<Coord> {
+        Some(Coord(
+            self.0.checked_add_signed(dx)?,
+            self.1.checked_add_signed(dy)?,
+        ))
+    }
+    pub fn neighbours4(self) -> impl Iterator<Item = Coord> {
+        Dir::ALL.into_iter().filter_map(move |dir| self.step(dir))
+    }
+    pub fn neighbours8(self) -> impl Iterator<Item = Coord> {
+        DELTAS_8
+            .into_iter()
+            .filter_map(move |(dx, dy)| self.offset(dx, dy))
+    }
+    pub fn manhattan(self, other: Coord) -> usize {
+        self.0.abs_diff(other.0) + self.1.abs_diff(other.1)
+    }
+}
+
+const DELTAS_8: [(isize, isize); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+/// Which neighbours of a cell count as connected to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connectivity {
+    /// Orthogonal neighbours only.
+    Four,
+    /// Orthogonal and diagonal neighbours.
+    Eight,
+}
+impl Connectivity {
+    fn deltas(self) -> &'static [(isize, isize)] {
+        match self {
+            Connectivity::Four => &DELTAS_4,
+            Connectivity::Eight => &DELTAS_8,
+        }
+    }
+}
+
+const DELTAS_4: [(isize, isize); 4] = [(0, -1), (0, 1), (1, 0), (-1, 0)];
+
+/// A compass direction. The discriminants are distinct bits so sets of
+/// directions, like the openings of a pipe, fit in a `u8` mask.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive)]
+#[repr(u8)]
+pub enum Dir {
+    N = 0b1000,
+    S = 0b0100,
+    E = 0b0010,
+    W = 0b0001,
+}
+impl Dir {
+    pub const ALL: [Dir; 4] = [Dir::N, Dir::S, Dir::E, Dir::W];
+
+    /// `(dx, dy)` of one step.
+    pub fn delta(self) -> (isize, isize) {
+        match self {
+            Dir::N => (0, -1),
+            Dir::S => (0, 1),
+            Dir::E => (1, 0),
+            Dir::W => (-1, 0),
+        }
+    }
+    pub fn reverse(self) -> Dir {
+        let odds = (self as u8) & 0b1010;
+        let evens = (self as u8) & 0b0101;
+        (odds >> 1 | evens << 1).try_into().unwrap()
+    }
+    pub fn turn_left(self) -> Dir {
+        match self {
+            Dir::N => Dir::W,
+            Dir::W => Dir::S,
+            Dir::S => Dir::E,
+            Dir::E => Dir::N,
+        }
+    }
+    pub fn turn_right(self) -> Dir {
+        self.turn_left().reverse()
+    }
+}
+
+/// A dense rectangular grid stored row-major in one `Vec`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Grid<T> {
+    width: usize,
+    height: usize,
+    cells: Vec<T>,
+}
+impl<T> Grid<T> {
+    pub fn new(width: usize, height: usize, fill: T) -> Self
+    where
+        T: Clone,
+    {
+        Grid {
+            width,
+            height,
+            cells: vec![fill; width * height],
+        }
+    }
+    /// Builds a grid from rows, or `None` if they differ in length.
+    pub fn from_rows(rows: Vec<Vec<T>>) -> Option<Self> {
+        let width = rows.first().map_or(0, Vec::len);
+        if rows.iter().any(|row| row.len() != width) {
+            return None;
+        }
+        Some(Grid {
+            width,
+            height: rows.len(),
+            cells: rows.into_iter().flatten().collect(),
+        })
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    pub fn contains(&self, coord: Coord) -> bool {
+        coord.0 < self.width && coord.1 < self.height
+    }
+    pub fn get(&self, coord: Coord) -> Option<&T> {
+        self.contains(coord)
+            .then(|| &self.cells[coord.1 * self.width + coord.0])
+    }
+    pub fn get_mut(&mut self, coord: Coord) -> Option<&mut T> {
+        self.contains(coord)
+            .then(|| &mut self.cells[coord.1 * self.width + coord.0])
+    }
+    /// The neighbouring coordinate in `dir`, if it is inside the grid.
+    pub fn step(&self, coord: Coord, dir: Dir) -> Option<Coord> {
+        coord.step(dir).filter(|&c| self.contains(c))
+    }
+    pub fn neighbours4(&self, coord: Coord) -> impl Iterator<Item = Coord> + '_ {
+        coord.neighbours4().filter(|&c| self.contains(c))
+    }
+    pub fn neighbours8(&self, coord: Coord) -> impl Iterator<Item = Coord> + '_ {
+        coord.neighbours8().filter(|&c| self.contains(c))
+    }
+    pub fn neighbours(
+        &self,
+        coord: Coord,
+        connectivity: Connectivity,
+    ) -> impl Iterator<Item = Coord> + '_ {
+        connectivity
+            .deltas()
+            .iter()
+            .filter_map(move |&(dx, dy)| coord.offset(dx, dy))
+            .filter(|&c| self.contains(c))
+    }
+    pub fn row(&self, y: usize) -> &[T] {
+        &self.cells[y * self.width..(y + 1) * self.width]
+    }
+    pub fn rows(&self) -> impl Iterator<Item = &[T]> {
+        self.cells.chunks(self.width.max(1))
+    }
+    pub fn column(&self, x: usize) -> impl Iterator<Item = &T> {
+        self.cells.iter().skip(x).step_by(self.width.max(1))
+    }
+    pub fn columns(&self) -> impl Iterator<Item = impl Iterator<Item = &T>> {
+        (0..self.width).map(|x| self.column(x))
+    }
+    /// Every coordinate, row by row.
+    pub fn coords(&self) -> impl Iterator<Item = Coord> {
+        let width = self.width;
+        (0..self.height).flat_map(move |y| (0..width).map(move |x| Coord(x, y)))
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (Coord, &T)> {
+        self.coords().zip(&self.cells)
+    }
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Coord, &mut T)> {
+        self.coords().zip(&mut self.cells)
+    }
+    pub fn position(&self, mut pred: impl FnMut(&T) -> bool) -> Option<Coord> {
+        self.iter().find_map(|(coord, t)| pred(t).then_some(coord))
+    }
+    pub fn map<U>(&self, f: impl FnMut(&T) -> U) -> Grid<U> {
+        Grid {
+            width: self.width,
+            height: self.height,
+            cells: self.cells.iter().map(f).collect(),
+        }
+    }
+    /// Draws the grid one character per cell.
+    pub fn render(&self, mut f: impl FnMut(&T) -> char) -> String {
+        let mut out = String::with_capacity((self.width + 1) * self.height);
+        for row in self.rows() {
+            out.extend(row.iter().map(&mut f));
+            out.push('\n');
+        }
+        out
+    }
+}
+impl<T: Clone> Grid<T> {
+    /// Builds a `width` by `height` grid taking each cell from the
+    /// coordinate `src` gives for it in `self`.
+    fn remap(&self, width: usize, height: usize, src: impl Fn(Coord) -> Coord) -> Grid<T> {
+        let coords = (0..height).flat_map(|y| (0..width).map(move |x| Coord(x, y)));
+        Grid {
+            width,
+            height,
+            cells: coords.map(|c| self[src(c)].clone()).collect(),
+        }
+    }
+    /// Mirrors along the main diagonal, so rows become columns.
+    pub fn transpose(&self) -> Grid<T> {
+        self.remap(self.height, self.width, |Coord(x, y)| Coord(y, x))
+    }
+    /// Rotates a quarter turn clockwise.
+    pub fn rotate_90(&self) -> Grid<T> {
+        let h = self.height;
+        self.remap(h, self.width, |Coord(x, y)| Coord(y, h - 1 - x))
+    }
+    pub fn rotate_180(&self) -> Grid<T> {
+        let (w, h) = (self.width, self.height);
+        self.remap(w, h, |Coord(x, y)| Coord(w - 1 - x, h - 1 - y))
+    }
+    /// Rotates a quarter turn anticlockwise.
+    pub fn rotate_270(&self) -> Grid<T> {
+        let w = self.width;
+        self.remap(self.height, w, |Coord(x, y)| Coord(w - 1 - y, x))
+    }
+    /// Mirrors left to right.
+    pub fn flip_horizontal(&self) -> Grid<T> {
+        let w = self.width;
+        self.remap(w, self.height, |Coord(x, y)| Coord(w - 1 - x, y))
+    }
+    /// Mirrors top to bottom.
+    pub fn flip_vertical(&self) -> Grid<T> {
+        let h = self.height;
+        self.remap(self.width, h, |Coord(x, y)| Coord(x, h - 1 - y))
+    }
+}
+impl<T: Hash> Grid<T> {
+    /// A hash of the dimensions and cells, for spotting a repeated state
+    /// without keeping every earlier grid around.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+impl<T> Index<Coord> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, coord: Coord) -> &T {
+        self.get(coord)
+            .unwrap_or_else(|| panic!("{coord:?} is outside the grid"))
+    }
+}
+impl<T> IndexMut<Coord> for Grid<T> {
+    fn index_mut(&mut self, coord: Coord) -> &mut T {
+        self.get_mut(coord)
+            .unwrap_or_else(|| panic!("{coord:?} is outside the grid"))
+    }
+}
+impl<T: Display> Display for Grid<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.rows() {
+            for cell in row {
+                cell.fmt(f)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// A set of connected cells, in the order they were reached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Region {
+    cells: Vec<Coord>,
+}
+impl Region {
+    pub fn cells(&self) -> &[Coord] {
+        &self.cells
+    }
+    pub fn area(&self) -> usize {
+        self.cells.len()
+    }
+    /// Number of cell edges facing a cell outside the region, counting
+    /// orthogonal neighbours only.
+    pub fn perimeter(&self) -> usize {
+        let cells = self.cells.iter().collect::<HashSet<_>>();
+        self.cells
+            .iter()
+            .flat_map(|&c| DELTAS_4.iter().map(move |&(dx, dy)| c.offset(dx, dy)))
+            .filter(|n| n.as_ref().is_none_or(|n| !cells.contains(n)))
+            .count()
+    }
+    /// Inclusive top-left and bottom-right corners, or `None` if empty.
+    pub fn bounding_box(&self) -> Option<(Coord, Coord)> {
+        let (xs, ys) = (
+            self.cells.iter().map(|c| c.0),
+            self.cells.iter().map(|c| c.1),
+        );
+        Some((
+            Coord(xs.clone().min()?, ys.clone().min()?),
+            Coord(xs.max()?, ys.max()?),
+        ))
+    }
+    /// Whether any cell lies on the outer edge of `grid`.
+    pub fn touches_boundary<T>(&self, grid: &Grid<T>) -> bool {
+        self.cells
+            .iter()
+            .any(|&Coord(x, y)| x == 0 || y == 0 || x + 1 == grid.width || y + 1 == grid.height)
+    }
+}
+
+/// Every cell of a grid labelled with the index of its region.
+#[derive(Debug, Clone)]
+pub struct Components {
+    pub labels: Grid<usize>,
+    pub regions: Vec<Region>,
+}
+
+impl<T> Grid<T> {
+    /// Cells reachable from `seed` through cells matching `pred`. Empty if
+    /// the seed itself is outside the grid or doesn't match.
+    pub fn flood_fill(
+        &self,
+        seed: Coord,
+        connectivity: Connectivity,
+        mut pred: impl FnMut(&T) -> bool,
+    ) -> Region {
+        if !self.get(seed).is_some_and(&mut pred) {
+            return Region { cells: Vec::new() };
+        }
+        let mut seen = Grid::new(self.width, self.height, false);
+        self.fill(seed, connectivity, &mut seen, |_, to| pred(to))
+    }
+
+    /// Splits the grid into regions where neighbouring cells belong together
+    /// when `joined` holds for them, e.g. `PartialEq::eq` for runs of equal
+    /// tiles. Regions are numbered in the order their first cell appears.
+    pub fn components(
+        &self,
+        connectivity: Connectivity,
+        mut joined: impl FnMut(&T, &T) -> bool,
+    ) -> Components {
+        let mut seen = Grid::new(self.width, self.height, false);
+        let mut labels = Grid::new(self.width, self.height, 0);
+        let mut regions = Vec::new();
+        for coord in self.coords() {
+            if seen[coord] {
+                continue;
+            }
+            let region = self.fill(coord, connectivity, &mut seen, &mut joined);
+            for &cell in &region.cells {
+                labels[cell] = regions.len();
+            }
+            regions.push(region);
+        }
+        Components { labels, regions }
+    }
+
+    /// Breadth-first fill from `seed`, marking cells in `seen` and stepping
+    /// only between cells for which `joined(from, to)` holds.
+    fn fill(
+        &self,
+        seed: Coord,
+        connectivity: Connectivity,
+        seen: &mut Grid<bool>,
+        mut joined: impl FnMut(&T, &T) -> bool,
+    ) -> Region {
+        let mut cells = vec![seed];
+        let mut queue = VecDeque::from([seed]);
+        seen[seed] = true;
+        while let Some(from) = queue.pop_front() {
+            for to in self.neighbours(from, connectivity) {
+                if !seen[to] && joined(&self[from], &self[to]) {
+                    seen[to] = true;
+                    cells.push(to);
+                    queue.push_back(to);
+                }
+            }
+        }
+        Region { cells }
+    }
+}
+
+/// A position on an unbounded plane, as `(x, y)` with `y` growing downwards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Point(pub isize, pub isize);
+impl Point {
+    pub fn step(self, dir: Dir) -> Point {
+        let (dx, dy) = dir.delta();
+        Point(self.0 + dx, self.1 + dy)
+    }
+    pub fn neighbours4(self) -> impl Iterator<Item = Point> {
+        DELTAS_4
+            .into_iter()
+            .map(move |(dx, dy)| Point(self.0 + dx, self.1 + dy))
+    }
+    pub fn neighbours8(self) -> impl Iterator<Item = Point> {
+        DELTAS_8
+            .into_iter()
+            .map(move |(dx, dy)| Point(self.0 + dx, self.1 + dy))
+    }
+    pub fn manhattan(self, other: Point) -> usize {
+        self.0.abs_diff(other.0) + self.1.abs_diff(other.1)
+    }
+}
+impl From<Coord> for Point {
+    fn from(Coord(x, y): Coord) -> Self {
+        Point(x as isize, y as isize)
+    }
+}
+impl TryFrom<Point> for Coord {
+    type Error = std::num::TryFromIntError;
+
+    fn try_from(Point(x, y): Point) -> Result<Self, Self::Error> {
+        Ok(Coord(x.try_into()?, y.try_into()?))
+    }
+}
+
+/// Cells scattered over an unbounded plane, for puzzles that grow past their
+/// input or into negative coordinates. Tracks the bounding box of the
+/// occupied cells as they are inserted and removed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SparseGrid<T> {
+    cells: HashMap<Point, T>,
+    bounds: Option<(Point, Point)>,
+}
+impl<T> Default for SparseGrid<T> {
+    fn default() -> Self {
+        SparseGrid {
+            cells: HashMap::new(),
+            bounds: None,
+        }
+    }
+}
+impl<T> SparseGrid<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Occupies every cell of `grid` matching `keep`, with the grid's
+    /// top-left corner placed at `origin`.
+    pub fn from_grid(grid: Grid<T>, origin: Point, mut keep: impl FnMut(&T) -> bool) -> Self {
+        let width = grid.width.max(1);
+        let mut sparse = Self::new();
+        for (i, t) in grid.cells.into_iter().enumerate() {
+            if keep(&t) {
+                let at = Point(
+                    origin.0 + (i % width) as isize,
+                    origin.1 + (i / width) as isize,
+                );
+                sparse.insert(at, t);
+            }
+        }
+        sparse
+    }
+    /// A dense copy of the occupied area, with empty cells set to `fill`,
+    /// and the point its top-left corner came from. `None` if empty.
+    pub fn to_grid(&self, fill: T) -> Option<(Point, Grid<T>)>
+    where
+        T: Clone,
+    {
+        let (min, max) = self.bounds?;
+        let mut grid = Grid::new(max.0.abs_diff(min.0) + 1, max.1.abs_diff(min.1) + 1, fill);
+        for (&Point(x, y), t) in &self.cells {
+            grid[Coord(x.abs_diff(min.0), y.abs_diff(min.1))] = t.clone();
+        }
+        Some((min, grid))
+    }
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+    /// Inclusive top-left and bottom-right corners of the occupied cells.
+    pub fn bounds(&self) -> Option<(Point, Point)> {
+        self.bounds
+    }
+    pub fn get(&self, at: Point) -> Option<&T> {
+        self.cells.get(&at)
+    }
+    pub fn get_mut(&mut self, at: Point) -> Option<&mut T> {
+        self.cells.get_mut(&at)
+    }
+    pub fn contains(&self, at: Point) -> bool {
+        self.cells.contains_key(&at)
+    }
+    /// Occupies `at`, returning what was there before.
+    pub fn insert(&mut self, at: Point, t: T) -> Option<T> {
+        self.bounds = Some(match self.bounds {
+            None => (at, at),
+            Some((min, max)) => (
+                Point(min.0.min(at.0), min.1.min(at.1)),
+                Point(max.0.max(at.0), max.1.max(at.1)),
+            ),
+        });
+        self.cells.insert(at, t)
+    }
+    /// Empties `at`. Shrinks the bounds if it was on their edge.
+    pub fn remove(&mut self, at: Point) -> Option<T> {
+        let t = self.cells.remove(&at)?;
+        if let Some((min, max)) = self.bounds {
+            if at.0 == min.0 || at.1 == min.1 || at.0 == max.0 || at.1 == max.1 {
+                self.bounds = self.cells.keys().fold(None, |bounds, &p| {
+                    let (min, max) = bounds.unwrap_or((p, p));
+                    Some((
+                        Point(min.0.min(p.0), min.1.min(p.1)),
+                        Point(max.0.max(p.0), max.1.max(p.1)),
+                    ))
+                });
+            }
+        }
+        Some(t)
+    }
+    /// Occupied cells in no particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (Point, &T)> {
+        self.cells.iter().map(|(&p, t)| (p, t))
+    }
+    /// Draws the occupied area one character per cell, passing `None` for
+    /// empty cells.
+    pub fn render(&self, mut f: impl FnMut(Option<&T>) -> char) -> String {
+        let Some((min, max)) = self.bounds else {
+            return String::new();
+        };
+        let mut out = String::new();
+        for y in min.1..=max.1 {
+            out.extend((min.0..=max.0).map(|x| f(self.get(Point(x, y)))));
+            out.push('\n');
+        }
+        out
+    }
+}
+impl<T> FromIterator<(Point, T)> for SparseGrid<T> {
+    fn from_iter<I: IntoIterator<Item = (Point, T)>>(iter: I) -> Self {
+        let mut sparse = Self::new();
+        for (at, t) in iter {
+            sparse.insert(at, t);
+        }
+        sparse
+    }
+}
+
+#[test]
+fn coords() {
+    assert_eq!(Coord(0, 3).step(Dir::W), None);
+    assert_eq!(Coord(2, 3).step(Dir::N), Some(Coord(2, 2)));
+    assert_eq!(Coord(2, 3).offset(-2, 4), Some(Coord(0, 7)));
+    assert_eq!(Coord(0, 0).neighbours4().count(), 2);
+    assert_eq!(Coord(0, 1).neighbours8().count(), 5);
+    assert_eq!(Coord(1, 1).manhattan(Coord(4, 0)), 4);
+    for dir in Dir::ALL {
+        assert_eq!(dir.reverse().reverse(), dir);
+        assert_eq!(dir.turn_left().turn_right(), dir);
+        let (dx, dy) = dir.delta();
+        assert_eq!(dir.reverse().delta(), (-dx, -dy));
+    }
+}
+
+#[test]
+fn parse_and_iterate() {
+    let mut input = "ab.\n.cd\n";
+    let grid = grid_p(|c| (c != '.').then_some(c).or(Some(' ')))
+        .parse_next(&mut input)
+        .unwrap();
+    assert_eq!((grid.width(), grid.height()), (3, 2));
+    assert_eq!(grid[Coord(2, 1)], 'd');
+    assert_eq!(grid.get(Coord(3, 0)), None);
+    assert_eq!(grid.row(1), [' ', 'c', 'd']);
+    assert_eq!(grid.column(1).collect::<String>(), "bc");
+    assert_eq!(grid.columns().count(), 3);
+    assert_eq!(grid.position(|&c| c == 'c'), Some(Coord(1, 1)));
+    assert_eq!(grid.neighbours8(Coord(0, 0)).count(), 3);
+    assert_eq!(grid.step(Coord(2, 1), Dir::E), None);
+    assert_eq!(grid.to_string(), "ab \n cd\n");
+    assert_eq!(
+        grid.render(|&c| if c == ' ' { '.' } else { c }),
+        "ab.\n.cd\n"
+    );
+}
+
+#[test]
+fn ragged_rows() {
+    let mut input = "ab\nc";
+    assert!(grid_p(Some).parse_next(&mut input).is_err());
+    assert!(Grid::from_rows(vec![vec![1], vec![2, 3]]).is_none());
+}
+
+#[cfg(test)]
+const PIPE_LOOP: &str = r#"...........
+.S-------7.
+.|F-----7|.
+.||.....||.
+.||.....||.
+.|L-7.F-J|.
+.|..|.|..|.
+.L--J.L--J.
+..........."#;
+
+#[test]
+fn flood_fill() {
+    let grid = grid_p(Some).parse(PIPE_LOOP).unwrap();
+    let outside = grid.flood_fill(Coord(0, 0), Connectivity::Four, |&c| c == '.');
+    assert_eq!(outside.area(), 49);
+    assert!(outside.touches_boundary(&grid));
+    assert_eq!(outside.bounding_box(), Some((Coord(0, 0), Coord(10, 8))));
+    let pocket = grid.flood_fill(Coord(2, 6), Connectivity::Four, |&c| c == '.');
+    assert_eq!(pocket.cells(), [Coord(2, 6), Coord(3, 6)]);
+    assert_eq!(pocket.perimeter(), 6);
+    assert!(!pocket.touches_boundary(&grid));
+    assert_eq!(
+        grid.flood_fill(Coord(1, 1), Connectivity::Four, |&c| c == '.'),
+        Region { cells: vec![] }
+    );
+    let pipe = grid.flood_fill(Coord(1, 1), Connectivity::Four, |&c| c != '.');
+    assert_eq!(pipe.bounding_box(), Some((Coord(1, 1), Coord(9, 7))));
+}
+
+#[test]
+fn components() {
+    let grid = grid_p(Some).parse(PIPE_LOOP).unwrap();
+    let ground = |conn| {
+        let Components { labels, regions } =
+            grid.components(conn, |&a, &b| (a == '.') == (b == '.'));
+        regions
+            .into_iter()
+            .filter(|region| grid[region.cells()[0]] == '.')
+            .inspect(|region| {
+                assert!(region
+                    .cells()
+                    .iter()
+                    .all(|&c| labels[c] == labels[region.cells()[0]]))
+            })
+            .collect::<Vec<_>>()
+    };
+    let four = ground(Connectivity::Four);
+    assert_eq!(four.len(), 3);
+    let enclosed = four.iter().filter(|r| !r.touches_boundary(&grid));
+    assert_eq!(enclosed.map(Region::area).sum::<usize>(), 4);
+    // The loop has no diagonal gaps, so corners don't join the pockets up.
+    let areas = |regions: Vec<Region>| regions.iter().map(Region::area).collect::<Vec<_>>();
+    assert_eq!(areas(ground(Connectivity::Eight)), areas(four));
+    // Without the pipes, everything is one region either way.
+    let open = grid.map(|_| ());
+    assert_eq!(
+        open.components(Connectivity::Four, |_, _| true)
+            .regions
+            .len(),
+        1
+    );
+}
+
+#[test]
+fn sparse_grid() {
+    let mut sparse = SparseGrid::new();
+    let mut at = Point(0, 0);
+    for dir in [
+        Dir::W,
+        Dir::W,
+        Dir::N,
+        Dir::E,
+        Dir::E,
+        Dir::E,
+        Dir::S,
+        Dir::S,
+    ] {
+        at = at.step(dir);
+        sparse.insert(at, '#');
+    }
+    assert_eq!(sparse.len(), 8);
+    assert_eq!(sparse.bounds(), Some((Point(-2, -1), Point(1, 1))));
+    assert_eq!(sparse.render(|t| *t.unwrap_or(&'.')), "####\n##.#\n...#\n");
+    assert_eq!(sparse.remove(Point(1, 1)), Some('#'));
+    assert_eq!(sparse.remove(Point(1, 1)), None);
+    assert_eq!(sparse.bounds(), Some((Point(-2, -1), Point(1, 0))));
+
+    let (origin, dense) = sparse.to_grid('.').unwrap();
+    assert_eq!(origin, Point(-2, -1));
+    assert_eq!(dense.to_string(), "####\n##.#\n");
+    let back = SparseGrid::from_grid(dense, origin, |&c| c == '#');
+    assert_eq!(back, sparse);
+
+    assert_eq!(Coord::try_from(Point(-1, 0)).ok(), None);
+    assert_eq!(
+        Coord::try_from(Point::from(Coord(3, 4))).ok(),
+        Some(Coord(3, 4))
+    );
+    assert_eq!(SparseGrid::<char>::new().render(|_| '.'), "");
+}
+
+#[test]
+fn transformations() {
+    let grid = grid_p(Some).parse("ab.\n.cd").unwrap();
+    let r = grid.rotate_90();
+    assert_eq!(r.to_string(), ".a\ncb\nd.\n");
+    assert_eq!(grid.transpose().to_string(), "a.\nbc\n.d\n");
+    assert_eq!(grid.flip_horizontal().to_string(), ".ba\ndc.\n");
+    assert_eq!(grid.flip_vertical().to_string(), ".cd\nab.\n");
+
+    let same = |a: &Grid<char>, b: &Grid<char>| a == b && a.content_hash() == b.content_hash();
+    assert!(same(&r.rotate_90().rotate_90().rotate_90(), &grid));
+    assert!(same(&r.rotate_90(), &grid.rotate_180()));
+    assert!(same(&r.rotate_90().rotate_90(), &grid.rotate_270()));
+    assert!(same(&grid.rotate_90().rotate_270(), &grid));
+    assert!(same(&grid.rotate_180().rotate_180(), &grid));
+    assert!(same(&grid.transpose().transpose(), &grid));
+    assert!(same(&grid.flip_horizontal().flip_horizontal(), &grid));
+    assert!(same(&grid.flip_vertical().flip_vertical(), &grid));
+    assert!(same(
+        &grid.flip_horizontal().flip_vertical(),
+        &grid.rotate_180()
+    ));
+    assert!(same(&grid.transpose().flip_horizontal(), &r));
+    assert!(same(&grid.flip_vertical().transpose(), &r));
+    assert_ne!(grid.content_hash(), r.content_hash());
+    // Same cells, different shape.
+    let row = Grid::from_rows(vec![vec!['a', 'b']]).unwrap();
+    assert_ne!(row.content_hash(), row.transpose().content_hash());
+}
@@ -1,130 +1,432 @@
-use std::{marker::PhantomData, ops::Range};
+// Column modes and span slicing are kept for diagnostics even where no day asks for them.
+#![allow(dead_code)]
 
-use line_span::LineSpans;
+use std::{
+    cell::{Cell, RefCell},
+    io::BufRead,
+    ops::{Deref, Range},
+    rc::Rc,
+};
+
+use unicode_width::UnicodeWidthChar;
 use winnow::{
-    combinator::fail,
-    error::ParserError,
-    stream::{Location, Offset, Stream, StreamIsPartial},
+    ascii::multispace0,
+    combinator::{eof, terminated},
+    error::{ContextError, ErrMode, StrContext, StrContextValue},
+    stream::{
+        AsBStr, Compare, CompareResult, FindSlice, Location, Offset, Stream, StreamIsPartial,
+    },
     PResult, Parser,
 };
 
 use std::fmt::Debug;
 
-#[derive(Debug)]
-pub struct LineSpan {
+/// A parsed value together with where it was found: the line it starts on
+/// and its column range, counted in the input's [`ColumnMode`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spanned<T> {
+    pub value: T,
     pub line: usize,
     pub span: Range<usize>,
 }
+impl<T> Spanned<T> {
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Spanned<U> {
+        Spanned {
+            value: f(self.value),
+            line: self.line,
+            span: self.span,
+        }
+    }
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+    /// Grid coordinate `(col, line)` of the first column of the span.
+    pub fn coord(&self) -> (usize, usize) {
+        (self.span.start, self.line)
+    }
+}
+impl<T> Deref for Spanned<T> {
+    type Target = T;
 
-pub fn linespan<P, O>(parser: P) -> LineSpanP<P, O> {
-    LineSpanP {
-        parser,
-        o: Default::default(),
+    fn deref(&self) -> &T {
+        &self.value
     }
 }
 
-pub struct LineSpanP<P, O> {
+pub fn spanned<P>(parser: P) -> SpannedP<P> {
+    SpannedP { parser }
+}
+pub struct SpannedP<P> {
     parser: P,
-    o: PhantomData<O>,
+}
+impl<I, P, O, E> Parser<LineLocated<I>, Spanned<O>, E> for SpannedP<P>
+where
+    I: LineStream,
+    P: Parser<LineLocated<I>, O, E>,
+{
+    fn parse_next(&mut self, input: &mut LineLocated<I>) -> PResult<Spanned<O>, E> {
+        let start = input.line_col(input.location());
+        self.parser.parse_next(input).map(|value| {
+            let end = input.column(start.line, input.location());
+            Spanned {
+                value,
+                line: start.line,
+                span: start.col..end,
+            }
+        })
+    }
 }
 
-impl<'i, P, O: Debug, E> Parser<LineLocated<'i>, LineSpan, E> for LineSpanP<P, O>
+/// Runs a parser written for the wrapped stream on a [`LineLocated`] one,
+/// for parsers that have no use for spans.
+pub fn unlocated<P>(parser: P) -> UnlocatedP<P> {
+    UnlocatedP { parser }
+}
+pub struct UnlocatedP<P> {
+    parser: P,
+}
+impl<I, P, O, E> Parser<LineLocated<I>, O, E> for UnlocatedP<P>
 where
-    P: Parser<LineLocated<'i>, O, E>,
-    E: ParserError<LineLocated<'i>>,
+    P: Parser<I, O, E>,
 {
-    fn parse_next(&mut self, input: &mut LineLocated<'i>) -> PResult<LineSpan, E> {
-        let start = input.location();
-        let line = input
-            .lines
-            .binary_search_by_key(&start, |r| r.end - 1)
-            .unwrap_or_else(|a| a);
-        if line == input.lines.len() {
-            return fail.parse_next(input);
+    fn parse_next(&mut self, input: &mut LineLocated<I>) -> PResult<O, E> {
+        self.parser.parse_next(&mut input.input)
+    }
+}
+
+pub const TAB_WIDTH: usize = 4;
+
+/// How columns are counted from the start of a line.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColumnMode {
+    /// UTF-8 bytes; cheap, and exact for ASCII input.
+    #[default]
+    Byte,
+    /// Unicode scalar values, matching grid coordinates built from `chars()`.
+    Char,
+    /// Terminal cells, with wide characters and tab stops of [`TAB_WIDTH`].
+    Display,
+}
+impl ColumnMode {
+    /// Column reached after `prefix`, which must start at a line start.
+    pub fn column(self, prefix: &str) -> usize {
+        match self {
+            ColumnMode::Byte => prefix.len(),
+            ColumnMode::Char => prefix.chars().count(),
+            ColumnMode::Display => prefix
+                .chars()
+                .fold(0, |width, c| width + cell_width(width, c)),
         }
-        let line_start = input.lines[line].start;
-        self.parser.parse_next(input).map(move |_| {
-            let end = input.location();
-            LineSpan {
-                line,
-                span: (start - line_start..end - line_start),
+    }
+    /// Like [`ColumnMode::column`], for a prefix of UTF-8 bytes.
+    pub fn column_of_bytes(self, prefix: &[u8]) -> usize {
+        match self {
+            ColumnMode::Byte => prefix.len(),
+            ColumnMode::Char => prefix.iter().filter(|&&b| b & 0xC0 != 0x80).count(),
+            ColumnMode::Display => self.column(&String::from_utf8_lossy(prefix)),
+        }
+    }
+    /// Byte offset into `line` of the character covering column `col`,
+    /// or the line length if `col` is past its end.
+    pub fn byte_offset(self, line: &str, col: usize) -> usize {
+        let mut column = 0;
+        for (offset, c) in line.char_indices() {
+            column += match self {
+                ColumnMode::Byte => c.len_utf8(),
+                ColumnMode::Char => 1,
+                ColumnMode::Display => cell_width(column, c),
+            };
+            if column > col {
+                return offset;
             }
-        })
+        }
+        line.len()
+    }
+    /// Re-expresses a column of `line` counted in `self` as one counted in `to`.
+    pub fn convert(self, line: &str, col: usize, to: ColumnMode) -> usize {
+        to.column(&line[..self.byte_offset(line, col)])
+    }
+}
+
+fn cell_width(column: usize, c: char) -> usize {
+    match c {
+        '\t' => TAB_WIDTH - column % TAB_WIDTH,
+        c => c.width().unwrap_or(0),
     }
 }
 
-pub fn with_linespan<P>(parser: P) -> WithLinespanP<P> {
-    WithLinespanP { parser }
+/// A position in a document as a zero-based line and a column into it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LineCol {
+    pub line: usize,
+    pub col: usize,
 }
-pub struct WithLinespanP<P> {
+
+/// A span that may cross line boundaries, kept both as line/column
+/// endpoints and as the absolute byte range into the document.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DocSpan {
+    pub start: LineCol,
+    pub end: LineCol,
+    pub bytes: Range<usize>,
+}
+impl DocSpan {
+    pub fn slice<'i>(&self, document: &'i str) -> &'i str {
+        &document[self.bytes.clone()]
+    }
+    pub fn lines<'i>(&self, document: &'i str) -> impl Iterator<Item = &'i str> {
+        self.slice(document).lines()
+    }
+    pub fn is_multiline(&self) -> bool {
+        self.start.line != self.end.line
+    }
+}
+impl std::fmt::Display for DocSpan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}-{}:{}",
+            self.start.line + 1,
+            self.start.col + 1,
+            self.end.line + 1,
+            self.end.col + 1
+        )
+    }
+}
+
+pub fn with_docspan<P>(parser: P) -> WithDocSpanP<P> {
+    WithDocSpanP { parser }
+}
+pub struct WithDocSpanP<P> {
     parser: P,
 }
-impl<'i, P, O, E> Parser<LineLocated<'i>, (O, LineSpan), E> for WithLinespanP<P>
+impl<I, P, O, E> Parser<LineLocated<I>, (O, DocSpan), E> for WithDocSpanP<P>
 where
-    P: Parser<LineLocated<'i>, O, E>,
-    E: ParserError<LineLocated<'i>>,
+    I: LineStream,
+    P: Parser<LineLocated<I>, O, E>,
 {
-    fn parse_next(&mut self, input: &mut LineLocated<'i>) -> PResult<(O, LineSpan), E> {
+    fn parse_next(&mut self, input: &mut LineLocated<I>) -> PResult<(O, DocSpan), E> {
         let start = input.location();
-        let line = input
-            .lines
-            .binary_search_by_key(&start, |r| r.end - 1)
-            .unwrap_or_else(|a| a);
-        if line == input.lines.len() {
-            return fail.parse_next(input);
-        }
-        let line_start = input.lines[line].start;
         self.parser.parse_next(input).map(move |output| {
             let end = input.location();
-            (
-                output,
-                LineSpan {
-                    line,
-                    span: (start - line_start..end - line_start),
-                },
-            )
+            let span = DocSpan {
+                start: input.line_col(start),
+                end: input.line_col(end),
+                bytes: start..end,
+            };
+            (output, span)
         })
     }
 }
 
-pub struct LineLocated<'i> {
-    initial: &'i str,
-    input: &'i str,
+/// Streams [`LineLocated`] can wrap: anything that can hand back its bytes
+/// and measure how far it has advanced, such as `&str` or `&[u8]`.
+pub trait LineStream: Clone + Stream + Offset + AsBStr {}
+impl<I: Clone + Stream + Offset + AsBStr> LineStream for I {}
+
+/// Byte ranges of every line in a document, newline included. Build it once
+/// per input and share it between every [`LineLocated`] over that input and
+/// any [`Diagnostic`] reported against it. A document that arrives in
+/// pieces can be indexed as it grows with [`LineIndex::push`], forgetting
+/// lines that are no longer needed with [`LineIndex::forget_before`].
+#[derive(Debug, Clone)]
+pub struct LineIndex {
+    /// The number of the line `lines[0]` covers.
+    first: usize,
     lines: Vec<Range<usize>>,
+    len: usize,
+    trailing_newline: bool,
 }
-impl<'i> LineLocated<'i> {
-    pub fn new(input: &'i str) -> Self {
-        let lines = input.line_spans().map(|ls| ls.range_with_ending()).collect();
+impl LineIndex {
+    /// How many lines past a lookup hint are scanned before falling back to
+    /// a binary search.
+    const SCAN: usize = 4;
+
+    pub fn new(document: &[u8]) -> Self {
+        let mut index = LineIndex {
+            first: 0,
+            lines: Vec::new(),
+            len: 0,
+            trailing_newline: false,
+        };
+        index.push(document);
+        index
+    }
+    /// Appends `text` to the document, continuing its last line if that
+    /// hadn't ended yet.
+    pub fn push(&mut self, text: &[u8]) {
+        let Some(&last_byte) = text.last() else {
+            return;
+        };
+        let mut rest = text;
+        if let Some(last) = self.lines.last_mut().filter(|_| !self.trailing_newline) {
+            let end = rest
+                .iter()
+                .position(|&b| b == b'\n')
+                .map_or(rest.len(), |i| i + 1);
+            last.end += end;
+            self.len += end;
+            rest = &rest[end..];
+        }
+        for line in rest.split_inclusive(|&b| b == b'\n') {
+            self.lines.push(self.len..self.len + line.len());
+            self.len += line.len();
+        }
+        self.trailing_newline = last_byte == b'\n';
+    }
+    /// Drops the ranges of every line before `line`. Their numbers stay
+    /// taken, so later lines keep theirs.
+    pub fn forget_before(&mut self, line: usize) {
+        let n = line.saturating_sub(self.first).min(self.lines.len());
+        self.lines.drain(..n);
+        self.first += n;
+    }
+    /// Lines seen so far, counting forgotten ones.
+    pub fn len(&self) -> usize {
+        self.first + self.lines.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    pub fn range(&self, line: usize) -> Option<Range<usize>> {
+        self.lines.get(line.checked_sub(self.first)?).cloned()
+    }
+    /// Start of `line`, or the end of the document for the line after the last.
+    pub fn line_start(&self, line: usize) -> usize {
+        self.range(line).map_or(self.len, |l| l.start)
+    }
+    /// Line containing byte `offset`, by binary search. Offsets in forgotten
+    /// lines give the first line still held.
+    pub fn line_of(&self, offset: usize) -> Option<usize> {
+        let line = self
+            .lines
+            .binary_search_by_key(&offset, |r| r.end - 1)
+            .unwrap_or_else(|a| a);
+        (line < self.lines.len()).then_some(self.first + line)
+    }
+    /// Line containing byte `offset`, looking a few lines forward from
+    /// `hint` first. Constant time for parsers that only move forward.
+    pub fn line_of_near(&self, offset: usize, hint: usize) -> Option<usize> {
+        let hint = hint.saturating_sub(self.first);
+        for line in hint..(hint + Self::SCAN).min(self.lines.len()) {
+            let range = &self.lines[line];
+            if offset < range.start {
+                break;
+            }
+            if offset < range.end {
+                return Some(self.first + line);
+            }
+        }
+        self.line_of(offset)
+    }
+    /// Line of `offset` for positions at or past the end of the document:
+    /// after the last character of the last line, or at the start of a new
+    /// line if the document ends with a newline.
+    fn line_past_end(&self) -> usize {
+        if self.trailing_newline || self.lines.is_empty() {
+            self.len()
+        } else {
+            self.len() - 1
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct LineLocated<I> {
+    initial: I,
+    input: I,
+    index: Rc<LineIndex>,
+    /// Where `initial` starts in the indexed document.
+    base: usize,
+    cursor: Cell<usize>,
+    columns: ColumnMode,
+    partial: bool,
+}
+impl<I: LineStream> LineLocated<I> {
+    pub fn new(input: I) -> Self {
+        let index = Rc::new(LineIndex::new(input.as_bstr()));
+        Self::with_index(input, index)
+    }
+    /// Wraps `input` reusing an index already built for it.
+    pub fn with_index(input: I, index: Rc<LineIndex>) -> Self {
+        Self::with_index_at(input, index, 0)
+    }
+    /// Wraps `input`, the end of a longer document starting at a line start
+    /// `base` bytes in. Spans and diagnostics are still counted from the
+    /// start of the whole document, which `index` covers up to the end of
+    /// `input`.
+    pub fn with_index_at(input: I, index: Rc<LineIndex>, base: usize) -> Self {
+        debug_assert_eq!(index.len, base + input.as_bstr().len());
         LineLocated {
-            lines,
-            initial: input,
+            cursor: Cell::new(index.first),
+            index,
+            base,
+            initial: input.clone(),
             input,
+            columns: ColumnMode::default(),
+            partial: false,
+        }
+    }
+    /// Counts the columns of produced spans in `columns` instead of bytes.
+    pub fn with_columns(mut self, columns: ColumnMode) -> Self {
+        self.columns = columns;
+        self
+    }
+    /// Marks the input as possibly cut short, for documents still arriving:
+    /// parsers that run into its end return `ErrMode::Incomplete` instead
+    /// of stopping there, and can be retried once more has been appended.
+    pub fn partial(mut self, partial: bool) -> Self {
+        self.partial = partial;
+        self
+    }
+    pub fn index(&self) -> &Rc<LineIndex> {
+        &self.index
+    }
+    fn column(&self, line: usize, offset: usize) -> usize {
+        let line_start = self.index.line_start(line).max(self.base);
+        self.columns
+            .column_of_bytes(&self.initial.as_bstr()[line_start - self.base..offset - self.base])
+    }
+    /// Line and column of a byte offset. The end of the document sits after
+    /// the last character of the last line, or at the start of a new line if
+    /// the document ends with a newline.
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        let line = match self.index.line_of_near(offset, self.cursor.get()) {
+            Some(line) => {
+                self.cursor.set(line);
+                line
+            }
+            None => self.index.line_past_end(),
+        };
+        LineCol {
+            line,
+            col: self.column(line, offset),
         }
     }
 }
-impl<'i> Debug for LineLocated<'i> {
+impl<I: Debug> Debug for LineLocated<I> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.input.fmt(f)
     }
 }
-impl<'i> Location for LineLocated<'i> {
+impl<I: LineStream> Location for LineLocated<I> {
     fn location(&self) -> usize {
-        self.input.offset_from(&self.initial)
+        self.base + self.input.offset_from(&self.initial)
     }
 }
-impl<'i> Offset<<&str as Stream>::Checkpoint> for LineLocated<'i> {
-    fn offset_from(&self, start: &<&str as Stream>::Checkpoint) -> usize {
+impl<I: Stream> Offset<<I as Stream>::Checkpoint> for LineLocated<I> {
+    fn offset_from(&self, start: &<I as Stream>::Checkpoint) -> usize {
         self.input.offset_from(start)
     }
 }
-impl<'i> Stream for LineLocated<'i> {
-    type Token = <&'i str as Stream>::Token;
+impl<I: LineStream> Stream for LineLocated<I> {
+    type Token = <I as Stream>::Token;
 
-    type Slice = <&'i str as Stream>::Slice;
+    type Slice = <I as Stream>::Slice;
 
-    type IterOffsets = <&'i str as Stream>::IterOffsets;
+    type IterOffsets = <I as Stream>::IterOffsets;
 
-    type Checkpoint = <&'i str as Stream>::Checkpoint;
+    type Checkpoint = <I as Stream>::Checkpoint;
 
     fn iter_offsets(&self) -> Self::IterOffsets {
         self.input.iter_offsets()
@@ -165,18 +467,631 @@ impl<'i> Stream for LineLocated<'i> {
         self.input.raw()
     }
 }
-impl<'i> StreamIsPartial for LineLocated<'i> {
-    type PartialState = <&'i str as StreamIsPartial>::PartialState;
+impl<I, T> Compare<T> for LineLocated<I>
+where
+    I: Compare<T>,
+{
+    fn compare(&self, t: T) -> CompareResult {
+        self.input.compare(t)
+    }
+
+    #[allow(deprecated)]
+    fn compare_no_case(&self, t: T) -> CompareResult {
+        self.input.compare_no_case(t)
+    }
+}
+impl<I, T> FindSlice<T> for LineLocated<I>
+where
+    I: FindSlice<T>,
+{
+    fn find_slice(&self, substr: T) -> Option<usize> {
+        self.input.find_slice(substr)
+    }
+}
+impl<I: StreamIsPartial> StreamIsPartial for LineLocated<I> {
+    type PartialState = (bool, <I as StreamIsPartial>::PartialState);
 
     fn complete(&mut self) -> Self::PartialState {
-        self.input.complete()
+        (std::mem::take(&mut self.partial), self.input.complete())
     }
 
-    fn restore_partial(&mut self, state: Self::PartialState) {
+    fn restore_partial(&mut self, (partial, state): Self::PartialState) {
+        self.partial = partial;
         self.input.restore_partial(state)
     }
 
     fn is_partial_supported() -> bool {
-        <&'i str as StreamIsPartial>::is_partial_supported()
+        true
+    }
+
+    fn is_partial(&self) -> bool {
+        self.partial || self.input.is_partial()
+    }
+}
+
+thread_local! {
+    static SOURCE: RefCell<String> = RefCell::new(String::from("<input>"));
+}
+
+/// Runs `f` with `name` as the file reported by any [`Diagnostic`] built inside it.
+pub fn with_source<R>(name: &str, f: impl FnOnce() -> R) -> R {
+    /// Puts the previous name back even if `f` panics.
+    struct Restore(String);
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            SOURCE.with(|s| std::mem::swap(&mut *s.borrow_mut(), &mut self.0));
+        }
+    }
+    let _restore = Restore(SOURCE.with(|s| s.replace(name.to_owned())));
+    f()
+}
+
+/// A parse failure located in the original document, rendered rustc-style
+/// with the offending line, a caret under the failing position and the
+/// winnow context that was active when the parser gave up.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    /// Character column, as editors count it.
+    pub column: usize,
+    /// Terminal column of the failing position within `source_line`.
+    pub display_column: usize,
+    /// The offending line with tabs expanded, so the caret lines up.
+    pub source_line: String,
+    pub context: Vec<StrContext>,
+}
+impl Diagnostic {
+    pub fn new(document: &str, offset: usize, error: &ContextError) -> Self {
+        let index = LineIndex::new(document.as_bytes());
+        Self::build(document, 0, &index, offset, error)
+    }
+    /// The failure `error` at the current position of `input`.
+    pub fn at(input: &LineLocated<&str>, error: &ContextError) -> Self {
+        Self::build(
+            input.initial,
+            input.base,
+            &input.index,
+            input.location(),
+            error,
+        )
+    }
+    /// `window` is the end of the indexed document, from `base` on.
+    fn build(
+        window: &str,
+        base: usize,
+        index: &LineIndex,
+        offset: usize,
+        error: &ContextError,
+    ) -> Self {
+        let line = index
+            .line_of(offset)
+            .unwrap_or(index.len().saturating_sub(1));
+        let range = index.range(line).unwrap_or(base..base);
+        let range = range.start.max(base) - base..range.end.max(base) - base;
+        let source_line = window[range.clone()].trim_end_matches(['\r', '\n']);
+        let prefix =
+            &source_line[..(offset - base).min(range.start + source_line.len()) - range.start];
+        Diagnostic {
+            file: SOURCE.with(|s| s.borrow().clone()),
+            line,
+            column: ColumnMode::Char.column(prefix),
+            display_column: ColumnMode::Display.column(prefix),
+            source_line: expand_tabs(source_line),
+            context: error.context().cloned().collect(),
+        }
+    }
+}
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut labels = self.context.iter().filter_map(|c| match c {
+            StrContext::Label(label) => Some(*label),
+            _ => None,
+        });
+        let expected = self
+            .context
+            .iter()
+            .filter_map(|c| match c {
+                StrContext::Expected(value) => Some(value.to_string()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let line_no = (self.line + 1).to_string();
+        let gutter = " ".repeat(line_no.len());
+        writeln!(f, "error: invalid {}", labels.next().unwrap_or("input"))?;
+        writeln!(
+            f,
+            "{gutter}--> {}:{}:{}",
+            self.file,
+            line_no,
+            self.column + 1
+        )?;
+        writeln!(f, "{gutter} |")?;
+        writeln!(f, "{line_no} | {}", self.source_line)?;
+        write!(f, "{gutter} | {}^", " ".repeat(self.display_column))?;
+        if !expected.is_empty() {
+            write!(f, " expected {}", expected.join(", "))?;
+        }
+        for label in labels {
+            write!(f, "\n{gutter} = while parsing {label}")?;
+        }
+        Ok(())
+    }
+}
+
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    for c in line.chars() {
+        match c {
+            '\t' => {
+                let width = cell_width(ColumnMode::Display.column(&expanded), c);
+                expanded.push_str(&" ".repeat(width));
+            }
+            c => expanded.push(c),
+        }
+    }
+    expanded
+}
+
+/// Runs `parser` over the whole of `input`, panicking with a rendered
+/// [`Diagnostic`] if it fails or leaves anything but whitespace.
+pub fn parse_or_report<'i, O, P>(mut input: &'i str, parser: P) -> O
+where
+    P: Parser<&'i str, O, ContextError>,
+{
+    let document = input;
+    match terminated(parser, end_p).parse_next(&mut input) {
+        Ok(output) => output,
+        Err(err) => {
+            let err = err
+                .into_inner()
+                .expect("complete input never needs more data");
+            panic!(
+                "\n{}",
+                Diagnostic::new(document, input.offset_from(&document), &err)
+            )
+        }
+    }
+}
+
+/// Like [`parse_or_report`], for parsers over a whole [`LineLocated`] document.
+pub fn parse_located_or_report<'i, O, P>(mut input: LineLocated<&'i str>, parser: P) -> O
+where
+    P: Parser<LineLocated<&'i str>, O, ContextError>,
+{
+    match terminated(parser, end_p).parse_next(&mut input) {
+        Ok(output) => output,
+        Err(err) => {
+            let err = err
+                .into_inner()
+                .expect("complete input never needs more data");
+            panic!("\n{}", Diagnostic::at(&input, &err))
+        }
     }
 }
+
+/// Reads a document one line at a time, so inputs larger than memory can be
+/// fed through the same parsers used on a whole document. Lines are
+/// appended to a partial [`LineLocated`] window as they arrive; a record
+/// that runs on past the end of the window is retried with the next line
+/// added, and lines are dropped once parsed. Spans and diagnostics are
+/// located in the whole document.
+pub struct LineReader<R> {
+    reader: R,
+    /// Lines read but not yet parsed, starting `base` bytes into the document.
+    window: String,
+    base: usize,
+    index: Rc<LineIndex>,
+    eof: bool,
+}
+impl<R: BufRead> LineReader<R> {
+    pub fn new(reader: R) -> Self {
+        LineReader {
+            reader,
+            window: String::new(),
+            base: 0,
+            index: Rc::new(LineIndex::new(b"")),
+            eof: false,
+        }
+    }
+    /// Byte offset of the start of the next line.
+    pub fn offset(&self) -> usize {
+        self.base
+    }
+    /// Appends the next line to the window, or returns false at the end of
+    /// the input.
+    fn read_line(&mut self) -> bool {
+        let start = self.window.len();
+        let read = self
+            .reader
+            .read_line(&mut self.window)
+            .expect("input is readable");
+        Rc::make_mut(&mut self.index).push(&self.window.as_bytes()[start..]);
+        read > 0
+    }
+    /// Parses the next record with `parser`, or returns `None` at the end of
+    /// the input. Panics with a [`Diagnostic`] if the record fails to parse.
+    pub fn parse_line<O, P>(&mut self, parser: P) -> Option<O>
+    where
+        P: for<'i> Parser<LineLocated<&'i str>, O, ContextError>,
+    {
+        self.try_parse_line(parser)
+            .map(|line| line.unwrap_or_else(|diagnostic| panic!("\n{diagnostic}")))
+    }
+    /// Parses the next record with `parser`, usually a single line, or
+    /// returns `None` at the end of the input. A record that fails to parse
+    /// is skipped over and described by the returned [`Diagnostic`].
+    pub fn try_parse_line<O, P>(&mut self, mut parser: P) -> Option<Result<O, Diagnostic>>
+    where
+        P: for<'i> Parser<LineLocated<&'i str>, O, ContextError>,
+    {
+        loop {
+            if !self.eof && !self.read_line() {
+                self.eof = true;
+            }
+            if self.window.is_empty() {
+                return None;
+            }
+            let mut input =
+                LineLocated::with_index_at(self.window.as_str(), self.index.clone(), self.base)
+                    .partial(!self.eof);
+            let output = match parser.parse_next(&mut input) {
+                Err(ErrMode::Incomplete(_)) if !self.eof => continue,
+                Ok(output) => {
+                    let _ = input.complete();
+                    end_p.parse_next(&mut input).map(|()| output)
+                }
+                Err(err) => Err(err),
+            };
+            let output = output.map_err(|err| {
+                let err = err
+                    .into_inner()
+                    .expect("complete input never needs more data");
+                Diagnostic::at(&input, &err)
+            });
+            drop(input);
+            self.base += self.window.len();
+            self.window.clear();
+            let index = Rc::make_mut(&mut self.index);
+            index.forget_before(index.len());
+            return Some(output);
+        }
+    }
+    /// Parses every remaining line with `parser` as the iterator is pulled,
+    /// holding only the current record. Lines that fail are skipped and
+    /// their diagnostics added to `report`, to be checked once the iterator
+    /// is done with.
+    pub fn parse_each<'r, O, P>(
+        mut self,
+        mut parser: P,
+        report: &'r mut ParseReport,
+    ) -> impl Iterator<Item = O> + 'r
+    where
+        R: 'r,
+        P: for<'i> Parser<LineLocated<&'i str>, O, ContextError> + 'r,
+    {
+        std::iter::from_fn(move || loop {
+            match self.try_parse_line(parser.by_ref())? {
+                Ok(output) => return Some(output),
+                Err(diagnostic) => report.failures.push(diagnostic),
+            }
+        })
+    }
+    /// Like [`LineReader::parse_all`], panicking with the whole report if any
+    /// line fails.
+    pub fn parse_all_or_report<O, P>(self, parser: P) -> Vec<O>
+    where
+        P: for<'i> Parser<LineLocated<&'i str>, O, ContextError>,
+    {
+        self.parse_all(parser)
+            .unwrap_or_else(|report| panic!("\n{report}"))
+    }
+    /// Parses every remaining line with `parser` into memory, carrying on
+    /// past failures so that all of them are reported together.
+    pub fn parse_all<O, P>(self, parser: P) -> Result<Vec<O>, ParseReport>
+    where
+        P: for<'i> Parser<LineLocated<&'i str>, O, ContextError>,
+    {
+        let mut report = ParseReport::default();
+        let parsed = self.parse_each(parser, &mut report).collect();
+        if report.failures.is_empty() {
+            Ok(parsed)
+        } else {
+            Err(report)
+        }
+    }
+}
+
+/// Every line of a document that failed to parse, in order.
+#[derive(Debug, Default)]
+pub struct ParseReport {
+    pub failures: Vec<Diagnostic>,
+}
+impl ParseReport {
+    /// Panics with the report if any line failed.
+    pub fn finish(self) {
+        if !self.failures.is_empty() {
+            panic!("\n{self}");
+        }
+    }
+}
+impl std::fmt::Display for ParseReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for diagnostic in &self.failures {
+            writeln!(f, "{diagnostic}\n")?;
+        }
+        match self.failures.len() {
+            1 => write!(f, "error: 1 line failed to parse"),
+            n => write!(f, "error: {n} lines failed to parse"),
+        }
+    }
+}
+
+fn end_p<I>(input: &mut I) -> PResult<()>
+where
+    I: Stream + StreamIsPartial,
+    <I as Stream>::Token: winnow::stream::AsChar + Clone,
+{
+    (multispace0, eof)
+        .void()
+        .context(StrContext::Expected(StrContextValue::Description(
+            "end of input",
+        )))
+        .parse_next(input)
+}
+
+#[test]
+fn docspan() {
+    use winnow::{ascii::digit1, combinator::separated};
+    let document = "a\n12\n34\n56\nb";
+    let mut input = LineLocated::new(document);
+    let numbers_p = separated::<_, _, (), _, _, _, _>(1.., digit1, '\n');
+    let (_, (_, span)) = ("a\n", with_docspan(numbers_p))
+        .parse_next(&mut input)
+        .map_err(|_: winnow::error::ErrMode<ContextError>| ())
+        .unwrap();
+    assert_eq!(span.start, LineCol { line: 1, col: 0 });
+    assert_eq!(span.end, LineCol { line: 3, col: 2 });
+    assert_eq!(span.slice(document), "12\n34\n56");
+    assert_eq!(span.lines(document).collect::<Vec<_>>(), ["12", "34", "56"]);
+    assert!(span.is_multiline());
+    assert_eq!(span.to_string(), "2:1-4:3");
+    assert_eq!(input.line_col(document.len()), LineCol { line: 4, col: 1 });
+    assert_eq!(
+        LineLocated::new("x\n").line_col(2),
+        LineCol { line: 1, col: 0 }
+    );
+}
+
+#[test]
+fn diagnostic() {
+    use winnow::{ascii::digit1, error::StrContextValue};
+    let document = "12 34\n56 x8\n";
+    let mut input = &document[6..];
+    let number = digit1
+        .context(StrContext::Label("number"))
+        .context(StrContext::Expected(StrContextValue::Description("digit")));
+    let err = (digit1, ' ', number)
+        .context(StrContext::Label("pair"))
+        .parse_next(&mut input)
+        .unwrap_err()
+        .into_inner()
+        .unwrap();
+    let diag = Diagnostic::new(document, input.offset_from(&document), &err);
+    assert_eq!((diag.line, diag.column), (1, 3));
+    assert_eq!(
+        diag.to_string(),
+        "error: invalid number\n --> <input>:2:4\n  |\n2 | 56 x8\n  |    ^ expected digit\n  = while parsing pair"
+    );
+}
+
+#[test]
+fn source_restored() {
+    let inner = || Diagnostic::new("x", 0, &ContextError::new()).file;
+    let result = std::panic::catch_unwind(|| with_source("a.txt", || panic!("parse failed")));
+    assert!(result.is_err());
+    assert_eq!(inner(), "<input>");
+    assert_eq!(with_source("b.txt", inner), "b.txt");
+    assert_eq!(inner(), "<input>");
+}
+
+#[test]
+fn column_modes() {
+    let line = "a\té€b";
+    assert_eq!(ColumnMode::Byte.column(line), 8);
+    assert_eq!(ColumnMode::Char.column(line), 5);
+    assert_eq!(ColumnMode::Display.column(line), 7);
+    assert_eq!(ColumnMode::Display.column("ab\tc"), 5);
+    assert_eq!(ColumnMode::Char.byte_offset(line, 4), 7);
+    assert_eq!(ColumnMode::Display.byte_offset(line, 2), 1);
+    assert_eq!(ColumnMode::Display.byte_offset(line, 9), line.len());
+    assert_eq!(ColumnMode::Char.convert(line, 4, ColumnMode::Display), 6);
+    assert_eq!(ColumnMode::Display.convert(line, 6, ColumnMode::Byte), 7);
+    assert_eq!(ColumnMode::Byte.convert(line, 7, ColumnMode::Char), 4);
+}
+
+#[test]
+fn char_columns() {
+    use winnow::token::take_until0;
+    let document = "ééx\n";
+    let mut input = LineLocated::new(document).with_columns(ColumnMode::Char);
+    let (_, x) = (take_until0("x"), spanned("x"))
+        .parse_next(&mut input)
+        .map_err(|_: winnow::error::ErrMode<ContextError>| ())
+        .unwrap();
+    assert_eq!(x.span, 2..3);
+}
+
+#[test]
+fn tabbed_diagnostic() {
+    let document = "\tab\u{e9}!";
+    let err = ContextError::new();
+    let diag = Diagnostic::new(document, document.len() - 1, &err);
+    assert_eq!((diag.column, diag.display_column), (4, 7));
+    assert!(diag.to_string().contains("1 |     abé!\n  |        ^"));
+}
+
+#[cfg(test)]
+fn spans_of(document: &str) -> Vec<Spanned<&str>> {
+    use winnow::{ascii::alpha0, combinator::separated};
+    let word_p = spanned(alpha0::<_, ContextError>);
+    separated(0.., word_p, '\n')
+        .parse_next(&mut LineLocated::new(document))
+        .unwrap()
+}
+
+#[test]
+fn spanned_lines() {
+    let spans = spans_of("ab\n\ncde");
+    let coords = spans.iter().map(|s| (s.coord(), s.span.len(), **s));
+    assert_eq!(
+        coords.collect::<Vec<_>>(),
+        [((0, 0), 2, "ab"), ((0, 1), 0, ""), ((0, 2), 3, "cde")]
+    );
+    let last = spans.into_iter().last().unwrap().map(str::len);
+    assert_eq!(
+        (last.line, last.span.clone(), last.into_inner()),
+        (2, 0..3, 3)
+    );
+}
+
+#[test]
+fn spanned_trailing_newline() {
+    let spans = spans_of("ab\n");
+    assert_eq!(spans.len(), 2);
+    assert_eq!((spans[1].coord(), spans[1].value), ((0, 1), ""));
+}
+
+#[test]
+fn byte_input() {
+    let text = "ab\r\n\u{e9}x\n";
+    let (from_str, from_bytes) = (LineLocated::new(text), LineLocated::new(text.as_bytes()));
+    assert_eq!(from_str.index.lines, [0..4, 4..8]);
+    assert_eq!(from_bytes.index.lines, from_str.index.lines);
+    let mut input = from_bytes.with_columns(ColumnMode::Char);
+    let (_, x) = (winnow::token::take_until0("x"), spanned(b'x'))
+        .parse_next(&mut input)
+        .map_err(|_: winnow::error::ErrMode<ContextError>| ())
+        .unwrap();
+    assert_eq!((x.coord(), x.value), ((1, 1), b'x'));
+}
+
+#[test]
+fn line_index() {
+    let index = LineIndex::new(b"ab\n\ncd\nef");
+    assert_eq!(index.len(), 4);
+    for offset in 0..10 {
+        let expected = [0, 0, 0, 1, 2, 2, 2, 3, 3].get(offset).copied();
+        assert_eq!(index.line_of(offset), expected);
+        for hint in 0..5 {
+            assert_eq!(index.line_of_near(offset, hint), expected);
+        }
+    }
+    assert_eq!(index.line_past_end(), 3);
+    assert_eq!(LineIndex::new(b"ab\n").line_past_end(), 1);
+    assert_eq!(LineIndex::new(b"").line_past_end(), 0);
+
+    let mut pushed = LineIndex::new(b"");
+    for piece in ["a", "b\n", "", "\ncd\ne", "f"] {
+        pushed.push(piece.as_bytes());
+    }
+    let whole = LineIndex::new(b"ab\n\ncd\nef");
+    assert_eq!((pushed.lines, pushed.len), (whole.lines, whole.len));
+    let mut forgetful = LineIndex::new(b"ab\n\ncd\nef");
+    forgetful.forget_before(2);
+    assert_eq!(forgetful.len(), 4);
+    assert_eq!(forgetful.range(1), None);
+    assert_eq!(forgetful.range(2), Some(4..7));
+    assert_eq!(forgetful.line_of(8), Some(3));
+    assert_eq!(forgetful.line_of_near(5, 0), Some(2));
+}
+
+#[test]
+fn line_reader() {
+    use std::io::BufReader;
+    use winnow::{ascii::dec_int, combinator::separated};
+    fn numbers_p(input: &mut &str) -> PResult<Vec<i64>> {
+        separated(1.., dec_int::<_, i64, _>, ' ').parse_next(input)
+    }
+    let document = "1 2 3\n-4 5\n6\n";
+    let reader = BufReader::with_capacity(2, document.as_bytes());
+    let mut report = ParseReport::default();
+    let mut lines = LineReader::new(reader).parse_each(unlocated(numbers_p), &mut report);
+    // Pulled one at a time; nothing past the current line is parsed yet.
+    assert_eq!(lines.next(), Some(vec![1, 2, 3]));
+    assert_eq!(lines.collect::<Vec<_>>(), [vec![-4, 5], vec![6]]);
+    report.finish();
+
+    let mut reader = LineReader::new("1 2\n3 x\n".as_bytes());
+    assert_eq!(reader.parse_line(unlocated(numbers_p)), Some(vec![1, 2]));
+    assert_eq!(reader.offset(), 4);
+    let err = std::panic::catch_unwind(move || reader.parse_line(unlocated(numbers_p)));
+    let err = err.unwrap_err();
+    let message = err.downcast_ref::<String>().unwrap();
+    assert!(message.contains("--> <input>:2:3"), "{message}");
+}
+
+#[test]
+fn incremental_records() {
+    use winnow::{ascii::dec_int, combinator::separated};
+    // Each record is a run of numbered lines closed by a `.` line, so it
+    // only parses once enough lines have arrived.
+    fn record_p(input: &mut LineLocated<&str>) -> PResult<Spanned<Vec<i64>>> {
+        let numbers = separated(1.., dec_int::<_, i64, _>, '\n');
+        spanned(terminated(numbers, "\n.")).parse_next(input)
+    }
+    let document = "1\n2\n.\n3\n.\n4\nx\n.\n5";
+    let mut reader = LineReader::new(document.as_bytes());
+    let first = reader.parse_line(record_p).unwrap();
+    assert_eq!((first.line, first.value.clone()), (0, vec![1, 2]));
+    assert_eq!(reader.offset(), 6);
+    let second = reader.parse_line(record_p).unwrap();
+    assert_eq!((second.line, second.value.clone()), (3, vec![3]));
+    // A failing record is dropped with every line read for it, leaving the
+    // stray `.` and the unterminated last record to fail on their own.
+    let failures = std::iter::from_fn(|| reader.try_parse_line(record_p));
+    let failures = failures.map(|r| r.map_err(|err| (err.line, err.column)));
+    assert_eq!(
+        failures.collect::<Vec<_>>(),
+        [Err((5, 1)), Err((7, 0)), Err((8, 1))]
+    );
+    assert_eq!(reader.index.len(), 9);
+    assert!(reader.index.lines.is_empty());
+}
+
+#[test]
+fn parse_report() {
+    use winnow::{ascii::dec_int, combinator::separated};
+    fn numbers_p(input: &mut &str) -> PResult<Vec<i64>> {
+        separated(1.., dec_int::<_, i64, _>, ' ')
+            .context(StrContext::Label("numbers"))
+            .parse_next(input)
+    }
+    let numbers_p = || unlocated(numbers_p);
+    let all = LineReader::new("1 2\n3\n".as_bytes()).parse_all(numbers_p());
+    assert_eq!(all.unwrap(), [vec![1, 2], vec![3]]);
+
+    let report = LineReader::new("1 2\n3 x\n4\ny\n5".as_bytes())
+        .parse_all(numbers_p())
+        .unwrap_err();
+    let lines = report.failures.iter().map(|d| d.line).collect::<Vec<_>>();
+    assert_eq!(lines, [1, 3]);
+    let message = report.to_string();
+    assert!(message.contains("--> <input>:2:3"), "{message}");
+    assert!(message.contains("--> <input>:4:1"), "{message}");
+    assert!(message.contains("error: invalid numbers"), "{message}");
+    assert!(
+        message.ends_with("error: 2 lines failed to parse"),
+        "{message}"
+    );
+
+    let mut report = ParseReport::default();
+    let reader = LineReader::new("1 2\nx\n3".as_bytes());
+    let sum = reader
+        .parse_each(numbers_p(), &mut report)
+        .flatten()
+        .sum::<i64>();
+    assert_eq!(sum, 6);
+    assert_eq!(report.failures.len(), 1);
+    assert!(std::panic::catch_unwind(|| report.finish()).is_err());
+}
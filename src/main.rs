@@ -1,4 +1,5 @@
 mod day1;
+mod day10;
 mod day2;
 mod day3;
 mod day4;
@@ -7,9 +8,22 @@ mod day6;
 mod day7;
 mod day8;
 mod day9;
-mod day10;
 
+mod bench;
+mod cycle;
+mod grid;
 mod linespan;
+mod math;
+mod parse;
+mod search;
+
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Seek},
+};
+
+use linespan::with_source;
+use tracing::{info_span, level_filters::LevelFilter};
 
 const DAYS: &[&dyn AocDay] = &[
     &day1::Day1,
@@ -27,34 +41,161 @@ const DAYS: &[&dyn AocDay] = &[
 fn main() {
     let mut args = std::env::args();
     let _program = args.next().unwrap();
-    let command = args.next().unwrap();
-    match &command[..3] {
+    let (flags, args): (Vec<_>, Vec<_>) = args.partition(|arg| arg.starts_with('-'));
+    let mut verbosity = 0;
+    let mut analyse = false;
+    for flag in &flags {
+        match flag.as_str() {
+            "-a" => analyse = true,
+            // `-v`, `-vv` and so on; anything past trace is still trace.
+            v if v.len() > 1 && v[1..].chars().all(|c| c == 'v') => verbosity += v.len() - 1,
+            _ => usage(&format!("unknown flag {flag}")),
+        }
+    }
+    init_tracing(verbosity);
+    let (command, path) = match args.as_slice() {
+        [] => usage("missing command"),
+        [command] => (command.as_str(), None),
+        [command, path] => (command.as_str(), Some(path)),
+        [_, _, extra, ..] => usage(&format!("unexpected argument {extra}")),
+    };
+    let no_path = || {
+        if let Some(path) = path {
+            usage(&format!("unexpected argument {path}"));
+        }
+    };
+    match command {
         "all" => {
+            no_path();
             for n in 0..DAYS.len() {
-                run_day(n + 1);
+                run_day(n + 1, analyse);
             }
         }
-        "day" => {
-            let day = command[3..].parse::<usize>().unwrap();
-            run_day(day);
+        "bench" => {
+            no_path();
+            bench::run();
+        }
+        _ => {
+            if let Some(day) = command.strip_prefix("day") {
+                let day = day_number(day);
+                no_path();
+                run_day(day, analyse);
+            } else if let Some(day) = command.strip_prefix("stream") {
+                let day = day_number(day);
+                if analyse {
+                    usage("-a needs the whole input, so it can't be used with stream");
+                }
+                let Some(path) = path else {
+                    usage("stream needs a file to read")
+                };
+                run_streamed(day, path);
+            } else {
+                usage(&format!("unknown command {command}"))
+            }
         }
-        _ => {}
     }
 }
 
-fn run_day(n: usize) {
+/// The day in a `dayN` or `streamN` command.
+fn day_number(day: &str) -> usize {
+    match day.parse() {
+        Ok(n) if (1..=DAYS.len()).contains(&n) => n,
+        _ => usage(&format!("no day {day:?}, expected 1 to {}", DAYS.len())),
+    }
+}
+
+const USAGE: &str = "usage: aoc-2023 [-v | -vv] [-a] (all | dayN | bench | streamN PATH)";
+
+/// Reports a bad command line and exits.
+fn usage(problem: &str) -> ! {
+    eprintln!("error: {problem}\n{USAGE}");
+    std::process::exit(2)
+}
+
+/// Installs a stderr subscriber for `-v` (debug) or `-vv` and up (trace).
+/// Without either flag no subscriber is installed, so every span and
+/// event is a disabled callsite and costs next to nothing.
+fn init_tracing(verbosity: usize) {
+    let level = match verbosity {
+        0 => return,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(std::io::stderr)
+        .with_span_events(tracing_subscriber::fmt::format::FmtSpan::CLOSE)
+        .init();
+}
+
+/// Runs both parts, then with `-a` prints whatever else the day can say
+/// about its input.
+fn run_day(n: usize, analyse: bool) {
     let day = DAYS[n - 1];
     let input = day.input();
+    let _day = info_span!("day", n).entered();
     println!("----------[Day {n}]----------");
-    let result = day.part1(input);
+    let file = format!("src/data/day{n}.txt");
+    let result = with_source(&file, || {
+        info_span!("part", n = 1).in_scope(|| day.part1(input))
+    });
     println!("Part 1: {result}");
-    let result = day.part2(input);
+    let result = with_source(&file, || {
+        info_span!("part", n = 2).in_scope(|| day.part2(input))
+    });
     println!("Part 2: {result}");
+    if analyse {
+        if let Some(analysis) = day.analysis(input) {
+            println!();
+            print!("{analysis}");
+        }
+    }
     println!();
 }
 
+/// Solves a line-oriented day straight from a file without reading it into
+/// memory, e.g. `stream7 huge.txt`. The file is read once per part, so it
+/// has to be seekable.
+fn run_streamed(n: usize, path: &str) {
+    let Some(day) = DAYS[n - 1].streaming() else {
+        usage(&format!("day {n} can't be solved from a stream"))
+    };
+    let mut input = BufReader::new(File::open(path).unwrap_or_else(|err| io_error(path, err)));
+    let _day = info_span!("day", n).entered();
+    println!("----------[Day {n}]----------");
+    let result = with_source(path, || {
+        info_span!("part", n = 1).in_scope(|| day.part1_stream(&mut input))
+    });
+    println!("Part 1: {result}");
+    input.rewind().unwrap_or_else(|err| io_error(path, err));
+    let result = with_source(path, || {
+        info_span!("part", n = 2).in_scope(|| day.part2_stream(&mut input))
+    });
+    println!("Part 2: {result}");
+    println!();
+}
+
+/// Reports a file that can't be read and exits.
+fn io_error(path: &str, err: std::io::Error) -> ! {
+    eprintln!("error: can't read {path}: {err}");
+    std::process::exit(1)
+}
+
 trait AocDay {
     fn input(&self) -> &'static str;
     fn part1(&self, input: &str) -> usize;
     fn part2(&self, input: &str) -> usize;
+    fn streaming(&self) -> Option<&dyn StreamingDay> {
+        None
+    }
+    /// Extra detail on the input printed after the answers, for `-a`.
+    fn analysis(&self, _input: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Days that only need one line of input at a time.
+trait StreamingDay {
+    fn part1_stream(&self, input: &mut dyn BufRead) -> usize;
+    fn part2_stream(&self, input: &mut dyn BufRead) -> usize;
 }
@@ -0,0 +1,181 @@
+// Number theory for puzzles to come; Day8 needs only lcm so far.
+#![allow(dead_code)]
+
+/// Greatest common divisor by Euclid's algorithm. `gcd(0, 0)` is 0.
+pub fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Least common multiple, or `None` if it doesn't fit in a `u64`. Divides
+/// before multiplying, so only a result that is itself too large fails.
+pub fn lcm(a: u64, b: u64) -> Option<u64> {
+    if a == 0 || b == 0 {
+        return Some(0);
+    }
+    (a / gcd(a, b)).checked_mul(b)
+}
+
+/// `(g, x, y)` with `a * x + b * y == g == gcd(a, b)`, `g` non-negative.
+pub fn ext_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    let (mut r0, mut r1) = (a, b);
+    let (mut x0, mut x1) = (1, 0);
+    let (mut y0, mut y1) = (0, 1);
+    while r1 != 0 {
+        let q = r0 / r1;
+        (r0, r1) = (r1, r0 - q * r1);
+        (x0, x1) = (x1, x0 - q * x1);
+        (y0, y1) = (y1, y0 - q * y1);
+    }
+    if r0 < 0 {
+        (-r0, -x0, -y0)
+    } else {
+        (r0, x0, y0)
+    }
+}
+
+/// The `x` in `0..m` with `a * x ≡ 1 (mod m)`, if `a` and `m` are coprime.
+/// `None` for a modulus that isn't positive.
+pub fn mod_inv(a: i128, m: i128) -> Option<i128> {
+    if m <= 0 {
+        return None;
+    }
+    let (g, x, _) = ext_gcd(a.rem_euclid(m), m);
+    (g == 1).then(|| x.rem_euclid(m))
+}
+
+/// Solves `x ≡ r (mod m)` for every `(r, m)` pair at once, returning the
+/// smallest non-negative `x` and the modulus it repeats with. The moduli
+/// need not be coprime; `None` if any modulus isn't positive, the
+/// congruences contradict each other or the combined modulus overflows.
+pub fn crt(congruences: &[(i128, i128)]) -> Option<(i128, i128)> {
+    congruences
+        .iter()
+        .try_fold((0, 1), |(r1, m1): (i128, i128), &(r2, m2)| {
+            if m2 <= 0 {
+                return None;
+            }
+            let (g, p, _) = ext_gcd(m1, m2);
+            let diff = r2 - r1;
+            if diff % g != 0 {
+                return None;
+            }
+            let step = m2 / g;
+            let m = m1.checked_mul(step)?;
+            let k = (diff / g % step).checked_mul(p % step)?.rem_euclid(step);
+            Some(((r1 + m1.checked_mul(k)?).rem_euclid(m), m))
+        })
+}
+
+/// `base.pow(exp) % m` by repeated squaring, without overflowing.
+///
+/// # Panics
+///
+/// If `m` is 0, like `%`.
+pub fn mod_pow(base: u64, mut exp: u64, m: u64) -> u64 {
+    assert!(m != 0, "mod_pow with a modulus of 0");
+    let m = m as u128;
+    let mut base = base as u128 % m;
+    let mut acc = 1 % m;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            acc = acc * base % m;
+        }
+        base = base * base % m;
+        exp >>= 1;
+    }
+    acc as u64
+}
+
+/// `n` values below `max` for the property tests below.
+#[cfg(test)]
+fn samples(n: usize, max: u64) -> impl Iterator<Item = u64> {
+    crate::bench::pseudo_random().take(n).map(move |x| x % max)
+}
+
+#[test]
+fn gcd_lcm() {
+    assert_eq!(gcd(0, 0), 0);
+    assert_eq!(gcd(0, 7), 7);
+    assert_eq!(gcd(7, 0), 7);
+    assert_eq!(gcd(1, u64::MAX), 1);
+    assert_eq!(lcm(0, 5), Some(0));
+    assert_eq!(lcm(u64::MAX, u64::MAX), Some(u64::MAX));
+    assert_eq!(lcm(u64::MAX, u64::MAX - 1), None);
+    let (a, b) = (samples(500, 1 << 20), samples(1000, 1 << 20).skip(500));
+    for (a, b) in a.zip(b) {
+        let g = gcd(a, b);
+        assert_eq!(g, gcd(b, a));
+        if g != 0 {
+            assert_eq!((a % g, b % g), (0, 0));
+            assert_eq!(gcd(a / g, b / g), 1);
+        }
+        let l = lcm(a, b).unwrap();
+        assert_eq!(g as u128 * l as u128, a as u128 * b as u128);
+    }
+}
+
+#[test]
+fn inverses() {
+    for (a, b) in samples(500, 1 << 40).zip(samples(1000, 1 << 40).skip(500)) {
+        let (a, b) = (a as i128 - (1 << 39), b as i128);
+        let (g, x, y) = ext_gcd(a, b);
+        assert_eq!(a * x + b * y, g);
+        assert_eq!(g as u64, gcd(a.unsigned_abs() as u64, b as u64));
+        if b > 1 {
+            match mod_inv(a, b) {
+                Some(inv) => assert_eq!((a * inv).rem_euclid(b), 1),
+                None => assert_ne!(g, 1),
+            }
+        }
+    }
+    assert_eq!(mod_inv(3, 7), Some(5));
+    assert_eq!(mod_inv(4, 8), None);
+    assert_eq!(mod_inv(3, 0), None);
+    assert_eq!(mod_inv(3, -7), None);
+}
+
+#[test]
+fn chinese_remainder() {
+    assert_eq!(crt(&[]), Some((0, 1)));
+    assert_eq!(crt(&[(2, 3), (3, 5), (2, 7)]), Some((23, 105)));
+    // Moduli sharing a factor: consistent, then contradictory.
+    assert_eq!(crt(&[(3, 4), (5, 6)]), Some((11, 12)));
+    assert_eq!(crt(&[(1, 4), (2, 6)]), None);
+    assert_eq!(crt(&[(1, 0)]), None);
+    assert_eq!(crt(&[(1, -3)]), None);
+    assert_eq!(crt(&[(2, 3), (1, -3)]), None);
+    let moduli = samples(200, 50).map(|m| m as i128 + 1).collect::<Vec<_>>();
+    for (ms, x) in moduli.chunks(4).zip(samples(50, 1 << 30)) {
+        let x = x as i128;
+        let congruences = ms.iter().map(|&m| (x % m, m)).collect::<Vec<_>>();
+        let (r, m) = crt(&congruences).unwrap();
+        assert_eq!(
+            m as u64,
+            ms.iter().fold(1, |l, &m| lcm(l, m as u64).unwrap())
+        );
+        assert_eq!(r, x % m);
+    }
+}
+
+#[test]
+fn modular_exponent() {
+    assert_eq!(mod_pow(2, 10, 1000), 24);
+    assert_eq!(mod_pow(5, 0, 1), 0);
+    assert_eq!(mod_pow(u64::MAX, u64::MAX, u64::MAX - 1), 1);
+    assert!(std::panic::catch_unwind(|| mod_pow(2, 3, 0)).is_err());
+    for ((b, e), m) in samples(200, 1000)
+        .zip(samples(400, 20).skip(200))
+        .zip(samples(600, 1 << 30).skip(400))
+    {
+        let m = m + 1;
+        let naive = (0..e).fold(1 % m as u128, |acc, _| acc * b as u128 % m as u128);
+        assert_eq!(mod_pow(b, e, m) as u128, naive);
+    }
+    // Fermat's little theorem.
+    for a in 1..100 {
+        assert_eq!(mod_pow(a, 1_000_000_006, 1_000_000_007), 1);
+    }
+}
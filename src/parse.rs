@@ -0,0 +1,162 @@
+// Input-shape helpers, not all of which the current days need.
+#![allow(dead_code)]
+
+use winnow::{
+    ascii::{dec_int, digit1, line_ending, space0, space1, Int},
+    combinator::{cut_err, peek, preceded, repeat, separated},
+    error::{
+        AddContext, ContextError, ErrMode, ErrorKind, ParserError, StrContext, StrContextValue,
+    },
+    prelude::*,
+    stream::{AsChar, Compare, ParseSlice, Stream, StreamIsPartial},
+    token::{any, none_of},
+};
+
+use crate::grid::Grid;
+
+/// Inputs the helpers run over: `&str`, or `LineLocated<&str>` when the
+/// caller wants spans.
+pub trait Text: StreamIsPartial + Stream<Token: AsChar + Clone> + Compare<&'static str> {}
+impl<I> Text for I where I: StreamIsPartial + Stream<Token: AsChar + Clone> + Compare<&'static str> {}
+
+/// Unsigned integers separated by spaces, like `79 14 55 13`.
+pub fn uints_p<I, O>(input: &mut I) -> PResult<Vec<O>>
+where
+    I: Text<Slice: ParseSlice<O>>,
+{
+    separated(1.., digit1.parse_to::<O>(), space1)
+        .context(StrContext::Expected(StrContextValue::Description(
+            "unsigned integer",
+        )))
+        .parse_next(input)
+}
+
+/// Optionally signed integers separated by spaces, like `10 -3 0`.
+pub fn ints_p<I: Text, O: Int>(input: &mut I) -> PResult<Vec<O>> {
+    separated(1.., dec_int::<_, O, _>, space1)
+        .context(StrContext::Expected(StrContextValue::Description(
+            "integer",
+        )))
+        .parse_next(input)
+}
+
+/// A `label: values` line, such as `Time:  7 15 30` with `uints_p`.
+pub fn labelled_p<I: Text, O>(
+    label: &'static str,
+    values: impl Parser<I, O, ContextError>,
+) -> impl Parser<I, O, ContextError> {
+    preceded((label, ':', space0), values).context(StrContext::Label(label))
+}
+
+/// One `line` per line of input, at least one.
+pub fn lines_p<I: Text, O>(
+    line: impl Parser<I, O, ContextError>,
+) -> impl Parser<I, Vec<O>, ContextError> {
+    separated(1.., line, line_ending)
+}
+
+/// Blocks separated by one or more blank lines, at least one.
+pub fn blocks_p<I: Text, O>(
+    block: impl Parser<I, O, ContextError>,
+) -> impl Parser<I, Vec<O>, ContextError> {
+    separated(1.., block, blank_lines_p)
+}
+
+/// The end of a line followed by at least one empty one.
+pub fn blank_lines_p<I: Text>(input: &mut I) -> PResult<()> {
+    (
+        line_ending,
+        repeat::<_, _, (), _, _>(1.., (space0, line_ending).void()),
+    )
+        .void()
+        .parse_next(input)
+}
+
+/// Parses lines of characters into a grid, mapping each one to a tile with
+/// `tile`. Cuts on an unmapped character, pointing at it, and on the first
+/// row that isn't as long as the first one, pointing at its start.
+pub fn grid_p<'i, T>(
+    tile: impl Fn(char) -> Option<T>,
+) -> impl FnMut(&mut &'i str) -> PResult<Grid<T>> {
+    move |input| {
+        let mut row_p = repeat(
+            1..,
+            preceded(peek(none_of(['\r', '\n'])), cut_err(any.verify_map(&tile))),
+        );
+        let first: Vec<T> = row_p.parse_next(input)?;
+        let width = first.len();
+        let mut rows = vec![first];
+        loop {
+            let before = input.checkpoint();
+            if line_ending::<_, ContextError>.parse_next(input).is_err() {
+                break;
+            }
+            let start = input.checkpoint();
+            match row_p.parse_next(input) {
+                Ok(row) if row.len() == width => rows.push(row),
+                Ok(_) => {
+                    input.reset(start);
+                    return Err(ErrMode::Cut(
+                        ContextError::from_error_kind(input, ErrorKind::Verify).add_context(
+                            input,
+                            StrContext::Label("grid with rows of equal length"),
+                        ),
+                    ));
+                }
+                // No further row, like an empty line: leave the newline.
+                Err(ErrMode::Backtrack(_)) => {
+                    input.reset(before);
+                    break;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(Grid::from_rows(rows).expect("rows have equal length"))
+    }
+}
+
+#[test]
+fn helpers() {
+    assert_eq!(uints_p::<_, u32>.parse("79 14  55"), Ok(vec![79, 14, 55]));
+    assert!(uints_p::<_, u32>.parse("79 -14").is_err());
+    assert_eq!(ints_p::<_, i64>.parse("10 -3 0"), Ok(vec![10, -3, 0]));
+    assert_eq!(
+        labelled_p("Time", uints_p::<_, usize>).parse("Time:      7  15   30"),
+        Ok(vec![7, 15, 30])
+    );
+    assert_eq!(
+        lines_p(ints_p::<_, i8>).parse("1 2\n-3\r\n4"),
+        Ok(vec![vec![1, 2], vec![-3], vec![4]])
+    );
+    let block = lines_p(uints_p::<_, u8>);
+    assert_eq!(
+        blocks_p(block).parse("1 2\n3\n\n4\n  \n\n5"),
+        Ok(vec![
+            vec![vec![1, 2], vec![3]],
+            vec![vec![4]],
+            vec![vec![5]]
+        ])
+    );
+}
+
+#[test]
+fn grids() {
+    let tile = |c: char| "#.".contains(c).then_some(c);
+    let grid = grid_p(tile).parse("#.\n.#").unwrap();
+    assert_eq!(grid.to_string(), "#.\n.#\n");
+
+    let mut input = "#.\n.x\n..";
+    let err = grid_p(tile).parse_next(&mut input).unwrap_err();
+    assert!(matches!(err, ErrMode::Cut(_)));
+    assert_eq!(input, "x\n..");
+    // Ragged rows cut at the start of the first one out of line.
+    for ragged in ["#.\n.#\n.\n#.", "#.\n.#\n...\n#."] {
+        let mut input = ragged;
+        let err = grid_p(tile).parse_next(&mut input).unwrap_err();
+        assert!(matches!(err, ErrMode::Cut(_)));
+        assert_eq!(input, &ragged[6..]);
+    }
+    let mut input = "#.\n.#\n\nrest";
+    assert_eq!(grid_p(tile).parse_next(&mut input).unwrap().height(), 2);
+    assert_eq!(input, "\n\nrest");
+}
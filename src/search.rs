@@ -0,0 +1,263 @@
+// Only bfs has a caller outside the tests so far.
+#![allow(dead_code)]
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    hash::Hash,
+    ops::Add,
+};
+
+/// Everything a search reached: the cost of each state, the state it was
+/// reached from, and the order they were visited in.
+#[derive(Debug, Clone)]
+pub struct Reached<S, C> {
+    costs: HashMap<S, C>,
+    parents: HashMap<S, S>,
+    order: Vec<S>,
+    found: Option<S>,
+}
+impl<S: Clone + Eq + Hash, C: Copy> Reached<S, C> {
+    fn new(start: S, zero: C) -> Self {
+        Reached {
+            costs: HashMap::from([(start, zero)]),
+            parents: HashMap::new(),
+            order: Vec::new(),
+            found: None,
+        }
+    }
+    /// The cost of the cheapest path found to `state`.
+    pub fn cost(&self, state: &S) -> Option<C> {
+        self.costs.get(state).copied()
+    }
+    /// All reached states and their costs.
+    pub fn costs(&self) -> &HashMap<S, C> {
+        &self.costs
+    }
+    /// States in the order the search visited them.
+    pub fn order(&self) -> &[S] {
+        &self.order
+    }
+    /// The goal state the search stopped at, if any.
+    pub fn found(&self) -> Option<&S> {
+        self.found.as_ref()
+    }
+    /// The states from the start to `state`, both included.
+    pub fn path_to(&self, state: &S) -> Option<Vec<S>> {
+        self.costs.get(state)?;
+        let mut path = vec![state.clone()];
+        while let Some(parent) = self.parents.get(path.last().unwrap()) {
+            path.push(parent.clone());
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+/// Breadth-first search from `start`, costing one per step. Stops at the
+/// first state matching `goal`; pass `|_| false` to reach everything.
+pub fn bfs<S, I>(
+    start: S,
+    mut successors: impl FnMut(&S) -> I,
+    mut goal: impl FnMut(&S) -> bool,
+) -> Reached<S, usize>
+where
+    S: Clone + Eq + Hash,
+    I: IntoIterator<Item = S>,
+{
+    let mut reached = Reached::new(start.clone(), 0);
+    let mut queue = VecDeque::from([(start, 0)]);
+    while let Some((state, cost)) = queue.pop_front() {
+        reached.order.push(state.clone());
+        if goal(&state) {
+            reached.found = Some(state);
+            break;
+        }
+        for next in successors(&state) {
+            if !reached.costs.contains_key(&next) {
+                reached.costs.insert(next.clone(), cost + 1);
+                reached.parents.insert(next.clone(), state.clone());
+                queue.push_back((next, cost + 1));
+            }
+        }
+    }
+    reached
+}
+
+/// Depth-first search from `start`, visiting successors in the order given.
+/// Costs are depths in the search tree, not shortest distances.
+pub fn dfs<S, I>(
+    start: S,
+    mut successors: impl FnMut(&S) -> I,
+    mut goal: impl FnMut(&S) -> bool,
+) -> Reached<S, usize>
+where
+    S: Clone + Eq + Hash,
+    I: IntoIterator<Item = S>,
+{
+    let mut reached = Reached::new(start.clone(), 0);
+    let mut stack = vec![(start, None)];
+    while let Some((state, parent)) = stack.pop() {
+        if let Some(parent) = parent {
+            if reached.costs.contains_key(&state) {
+                continue;
+            }
+            let cost = reached.costs[&parent] + 1;
+            reached.costs.insert(state.clone(), cost);
+            reached.parents.insert(state.clone(), parent);
+        }
+        reached.order.push(state.clone());
+        if goal(&state) {
+            reached.found = Some(state);
+            break;
+        }
+        let next = successors(&state).into_iter().collect::<Vec<_>>();
+        for next in next.into_iter().rev() {
+            if !reached.costs.contains_key(&next) {
+                stack.push((next, Some(state.clone())));
+            }
+        }
+    }
+    reached
+}
+
+/// Cheapest paths from `start` where each successor comes with the cost of
+/// the step to it. `C::default()` is taken as zero.
+pub fn dijkstra<S, C, I>(
+    start: S,
+    successors: impl FnMut(&S) -> I,
+    goal: impl FnMut(&S) -> bool,
+) -> Reached<S, C>
+where
+    S: Clone + Eq + Hash,
+    C: Copy + Ord + Default + Add<Output = C>,
+    I: IntoIterator<Item = (S, C)>,
+{
+    astar(start, successors, |_| C::default(), goal)
+}
+
+/// Dijkstra guided by `heuristic`, which must never overestimate the
+/// remaining cost to a goal for the result to be cheapest.
+pub fn astar<S, C, I>(
+    start: S,
+    mut successors: impl FnMut(&S) -> I,
+    mut heuristic: impl FnMut(&S) -> C,
+    mut goal: impl FnMut(&S) -> bool,
+) -> Reached<S, C>
+where
+    S: Clone + Eq + Hash,
+    C: Copy + Ord + Default + Add<Output = C>,
+    I: IntoIterator<Item = (S, C)>,
+{
+    let mut reached = Reached::new(start.clone(), C::default());
+    // States live beside the heap so they needn't be `Ord`.
+    let mut states = vec![start.clone()];
+    let mut heap = BinaryHeap::from([Reverse((heuristic(&start), C::default(), 0))]);
+    while let Some(Reverse((_, cost, i))) = heap.pop() {
+        let state = states[i].clone();
+        if reached.costs[&state] < cost {
+            continue;
+        }
+        reached.order.push(state.clone());
+        if goal(&state) {
+            reached.found = Some(state);
+            break;
+        }
+        for (next, step) in successors(&state) {
+            let next_cost = cost + step;
+            if reached.costs.get(&next).is_some_and(|&c| c <= next_cost) {
+                continue;
+            }
+            reached.costs.insert(next.clone(), next_cost);
+            reached.parents.insert(next.clone(), state.clone());
+            heap.push(Reverse((
+                next_cost + heuristic(&next),
+                next_cost,
+                states.len(),
+            )));
+            states.push(next);
+        }
+    }
+    reached
+}
+
+#[cfg(test)]
+fn maze() -> crate::grid::Grid<char> {
+    use winnow::Parser;
+
+    crate::parse::grid_p(Some)
+        .parse(
+            "S..#....\n\
+             .#.#.##.\n\
+             .#...#..\n\
+             .####.#.\n\
+             ......#E",
+        )
+        .unwrap()
+}
+
+#[test]
+fn unweighted() {
+    use crate::grid::Coord;
+
+    let grid = maze();
+    let open = |&c: &Coord| {
+        grid.neighbours4(c)
+            .filter(|&n| grid[n] != '#')
+            .collect::<Vec<_>>()
+    };
+    let end = grid.position(|&c| c == 'E').unwrap();
+    let all = bfs(Coord(0, 0), open, |_| false);
+    assert_eq!(all.found(), None);
+    assert_eq!(all.cost(&end), Some(15));
+    assert_eq!(
+        all.costs().len(),
+        grid.iter().filter(|(_, &c)| c != '#').count()
+    );
+
+    let early = bfs(Coord(0, 0), open, |&c| c == Coord(2, 0));
+    assert_eq!(early.found(), Some(&Coord(2, 0)));
+    assert!(early.order().len() < all.order().len());
+    assert_eq!(
+        early.path_to(&Coord(2, 0)),
+        Some(vec![Coord(0, 0), Coord(1, 0), Coord(2, 0)])
+    );
+
+    let deep = dfs(Coord(0, 0), open, |_| false);
+    assert_eq!(deep.order()[..3], [Coord(0, 0), Coord(0, 1), Coord(0, 2)]);
+    assert_eq!(deep.order().len(), all.order().len());
+    let path = deep.path_to(&end).unwrap();
+    assert!(path.windows(2).all(|w| w[0].manhattan(w[1]) == 1));
+    assert_eq!(deep.cost(&end), Some(path.len() - 1));
+}
+
+#[test]
+fn weighted() {
+    use crate::grid::Coord;
+    use winnow::Parser;
+
+    // Each step costs the digit of the cell it enters.
+    let grid = crate::parse::grid_p(|c: char| c.to_digit(10))
+        .parse("1163\n1381\n2136\n3694")
+        .unwrap();
+    let steps = |&c: &Coord| {
+        grid.neighbours4(c)
+            .map(|n| (n, grid[n]))
+            .collect::<Vec<_>>()
+    };
+    let end = Coord(3, 3);
+    let d = dijkstra(Coord(0, 0), steps, |&c| c == end);
+    assert_eq!(d.cost(&end), Some(17));
+    let path = d.path_to(&end).unwrap();
+    assert_eq!(path.iter().skip(1).map(|&c| grid[c]).sum::<u32>(), 17);
+
+    let a = astar(
+        Coord(0, 0),
+        steps,
+        |&c| c.manhattan(end) as u32,
+        |&c| c == end,
+    );
+    assert_eq!(a.cost(&end), Some(17));
+    assert!(a.order().len() <= d.order().len());
+    assert_eq!(dijkstra(end, steps, |&c| c == Coord(9, 9)).found(), None);
+}